@@ -1,9 +1,6 @@
 use anyhow::Result;
 use fs_usage_sys::{FsEvent, FsUsageMonitorBuilder};
 use std::env;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
 
 fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
@@ -27,35 +24,13 @@ fn main() -> Result<()> {
         .exclude_process("fs_usage")
         .exclude_process("kernel_task");
 
-    let mut monitor = builder.build()?;
+    let mut monitor = builder
+        .on_event(print_event)
+        .on_error(|e| eprintln!("Monitor error: {}", e))
+        .build()?;
 
-    println!("Starting file system monitor...");
-    monitor.start()?;
-
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-
-    ctrlc::set_handler(move || {
-        println!("\nStopping monitor...");
-        r.store(false, Ordering::SeqCst);
-    })?;
-
-    while running.load(Ordering::SeqCst) {
-        match monitor.events().recv_timeout(Duration::from_millis(100)) {
-            Ok(event) => {
-                print_event(&event);
-            }
-            Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
-                continue;
-            }
-            Err(e) => {
-                eprintln!("Error receiving event: {}", e);
-                break;
-            }
-        }
-    }
-
-    monitor.stop()?;
+    println!("Starting file system monitor (Ctrl-C to stop)...");
+    monitor.run_until_ctrl_c()?;
     println!("Monitor stopped");
 
     Ok(())
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use fs_usage_sys::{FsEvent, FsUsageMonitorBuilder};
+use fs_usage_sys::{ActorKind, Attributor, FsUsageMonitorBuilder};
 use std::env;
 use std::process;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -17,7 +17,8 @@ fn main() -> Result<()> {
         .exclude_pid(current_pid)
         .exclude_process("mds")
         .exclude_process("mdworker")
-        .exclude_process("Spotlight");
+        .exclude_process("Spotlight")
+        .attributor(Attributor::default());
 
     if args.len() > 1 {
         for path in &args[1..] {
@@ -46,32 +47,37 @@ fn main() -> Result<()> {
         r.store(false, Ordering::SeqCst);
     })?;
 
-    let mut claude_events = Vec::new();
+    let mut agent_events = Vec::new();
     let mut editor_events = Vec::new();
     let mut other_events = Vec::new();
 
     while running.load(Ordering::SeqCst) {
         match monitor.events().recv_timeout(Duration::from_millis(100)) {
             Ok(event) => {
-                categorize_event(
-                    &event,
-                    &mut claude_events,
-                    &mut editor_events,
-                    &mut other_events,
-                );
-
                 println!("\n--- Event ---");
                 println!("Process: {} (PID: {})", event.process_name, event.pid);
                 println!("Operation: {}", event.operation);
                 println!("Path: {}", event.path);
                 println!("Result: {}", event.result);
 
-                if is_claude_process(&event.process_name) {
-                    println!("⚠️  CLAUDE DETECTED - File change by AI assistant");
-                } else if is_editor_process(&event.process_name) {
-                    println!("✏️  EDITOR - File change by text editor");
-                } else {
-                    println!("🔧 OTHER - File change by: {}", event.process_name);
+                let kind = event.actor.as_ref().map(|actor| actor.kind);
+                let actor_name = event
+                    .actor
+                    .as_ref()
+                    .map_or(event.process_name.as_str(), |actor| actor.name.as_str());
+                match kind {
+                    Some(ActorKind::AiAgent) => {
+                        println!("⚠️  AI AGENT - File change by {}", actor_name);
+                        agent_events.push(event);
+                    }
+                    Some(ActorKind::HumanEditor) => {
+                        println!("✏️  EDITOR - File change by {}", actor_name);
+                        editor_events.push(event);
+                    }
+                    _ => {
+                        println!("🔧 OTHER - File change by: {}", actor_name);
+                        other_events.push(event);
+                    }
                 }
             }
             Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
@@ -87,34 +93,9 @@ fn main() -> Result<()> {
     monitor.stop()?;
 
     println!("\n=== Summary ===");
-    println!("Claude events: {}", claude_events.len());
+    println!("AI agent events: {}", agent_events.len());
     println!("Editor events: {}", editor_events.len());
     println!("Other events: {}", other_events.len());
 
     Ok(())
 }
-
-fn is_claude_process(process_name: &str) -> bool {
-    let claude_processes = ["node", "electron", "Cursor", "Code", "code"];
-    claude_processes.iter().any(|&p| process_name.contains(p))
-}
-
-fn is_editor_process(process_name: &str) -> bool {
-    let editor_processes = ["vim", "nvim", "emacs", "nano", "TextEdit", "Sublime Text"];
-    editor_processes.iter().any(|&p| process_name.contains(p))
-}
-
-fn categorize_event(
-    event: &FsEvent,
-    claude_events: &mut Vec<FsEvent>,
-    editor_events: &mut Vec<FsEvent>,
-    other_events: &mut Vec<FsEvent>,
-) {
-    if is_claude_process(&event.process_name) {
-        claude_events.push(event.clone());
-    } else if is_editor_process(&event.process_name) {
-        editor_events.push(event.clone());
-    } else {
-        other_events.push(event.clone());
-    }
-}
@@ -0,0 +1,332 @@
+use crate::classifier::OperationClassifier;
+use crate::path_matcher::PathKeys;
+use crate::{FsEvent, OperationType};
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Upper bound on tracked temp files and backups, cleared when exceeded.
+const MAX_TRACKED_FILES: usize = 4096;
+
+/// A change reconstructed from several raw events, produced by
+/// `FsUsageMonitor::logical_changes()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LogicalChange {
+    /// `path` was replaced with new content written to `via_temp`, either by
+    /// renaming a temp file over it or by renaming it to a backup and
+    /// rewriting it. Attributed to the process that wrote the new content.
+    Modified {
+        path: String,
+        via_temp: String,
+        pid: u32,
+        process_name: String,
+        timestamp: String,
+    },
+}
+
+struct Writer {
+    pid: u32,
+    process_name: String,
+}
+
+/// Recognizes atomic saves in the event stream:
+///
+/// - write `foo.rs.tmp1234` (or `.foo.rs.swp`, `foo.rs~`, ...), then rename it
+///   over `foo.rs`;
+/// - rename `foo.rs` to a backup such as `foo.rs~`, then write `foo.rs`
+///   again, as vim and emacs do.
+///
+/// fs_usage prints data writes with volume-relative paths but renames with
+/// absolute ones, so files are tracked by their `PathKeys` key.
+pub(crate) struct AtomicSaveDetector {
+    classifier: OperationClassifier,
+    keys: PathKeys,
+    temp_writers: HashMap<String, Writer>,
+    backups: HashMap<(u32, String), String>,
+}
+
+impl AtomicSaveDetector {
+    pub(crate) fn new(classifier: OperationClassifier, path_roots: &[String]) -> Self {
+        Self {
+            classifier,
+            keys: PathKeys::new(path_roots),
+            temp_writers: HashMap::new(),
+            backups: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn process(&mut self, event: &FsEvent) -> Option<LogicalChange> {
+        if event.result != "OK" {
+            return None;
+        }
+
+        if event.operation.starts_with("rename") {
+            let destination = event.target_path.clone()?;
+            return self.rename(event, event.path.clone(), destination);
+        }
+
+        if self
+            .classifier
+            .matches(&OperationType::Delete, &event.operation)
+        {
+            self.temp_writers.remove(&self.keys.key(&event.path));
+            return None;
+        }
+
+        let writes = self.classifier.matches_event(&OperationType::Write, event)
+            || self.classifier.matches_event(&OperationType::Create, event);
+        if !writes {
+            return None;
+        }
+
+        let key = self.keys.key(&event.path);
+        if let Some(backup) = self.backups.remove(&(event.pid, key.clone())) {
+            return Some(modified(event, event.path.clone(), backup, None));
+        }
+
+        if is_temp_path(&event.path) && !self.temp_writers.contains_key(&key) {
+            if self.temp_writers.len() >= MAX_TRACKED_FILES {
+                self.temp_writers.clear();
+            }
+            self.temp_writers.insert(
+                key,
+                Writer {
+                    pid: event.pid,
+                    process_name: event.process_name.clone(),
+                },
+            );
+        }
+        None
+    }
+
+    fn rename(
+        &mut self,
+        event: &FsEvent,
+        source: String,
+        destination: String,
+    ) -> Option<LogicalChange> {
+        if let Some(writer) = self.temp_writers.remove(&self.keys.key(&source)) {
+            return Some(modified(event, destination, source, Some(writer)));
+        }
+        if is_temp_path(&source) && !is_temp_path(&destination) {
+            return Some(modified(event, destination, source, None));
+        }
+
+        if is_temp_path(&destination) && !is_temp_path(&source) {
+            if self.backups.len() >= MAX_TRACKED_FILES {
+                self.backups.clear();
+            }
+            self.backups
+                .insert((event.pid, self.keys.key(&source)), destination);
+        }
+        None
+    }
+}
+
+fn modified(
+    event: &FsEvent,
+    path: String,
+    via_temp: String,
+    writer: Option<Writer>,
+) -> LogicalChange {
+    let writer = writer.unwrap_or_else(|| Writer {
+        pid: event.pid,
+        process_name: event.process_name.clone(),
+    });
+    LogicalChange::Modified {
+        path,
+        via_temp,
+        pid: writer.pid,
+        process_name: writer.process_name,
+        timestamp: event.timestamp.clone(),
+    }
+}
+
+/// Whether the file name looks like an editor or tool temp/backup file.
+pub(crate) fn is_temp_path(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if name.is_empty() {
+        return false;
+    }
+
+    name.ends_with('~')
+        || (name.starts_with('#') && name.ends_with('#'))
+        || (name.starts_with('.') && (name.ends_with(".swp") || name.ends_with(".swx")))
+        || name.contains(".sb-")
+        || name.ends_with(".tmp")
+        || name
+            .match_indices(".tmp")
+            .any(|(i, _)| is_temp_suffix(&name[i + ".tmp".len()..]))
+}
+
+/// `.tmp` followed by a numeric or random suffix, e.g. `.tmp1234`,
+/// `.tmp.5f3a`, `.tmp-Ab12` or tempfile's six-character `.tmpXy12Ab`.
+fn is_temp_suffix(suffix: &str) -> bool {
+    let alphanumeric = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric());
+    match suffix.strip_prefix(['.', '-', '_']) {
+        Some(rest) => alphanumeric(rest),
+        None => {
+            (!suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()))
+                || (suffix.len() == 6 && alphanumeric(suffix))
+        }
+    }
+}
+
+/// Feeds `events` through an `AtomicSaveDetector`. Returns when either side
+/// is dropped.
+pub(crate) fn run_detector(
+    events: &Receiver<FsEvent>,
+    changes: &Sender<LogicalChange>,
+    classifier: OperationClassifier,
+    path_roots: &[String],
+) {
+    let mut detector = AtomicSaveDetector::new(classifier, path_roots);
+    for event in events.iter() {
+        if let Some(change) = detector.process(&event) {
+            if changes.send(change).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(pid: u32, operation: &str, path: &str) -> FsEvent {
+        FsEvent {
+            timestamp: "10:00:00.000".to_string(),
+            process_name: format!("proc{}", pid),
+            pid,
+            operation: operation.to_string(),
+            path: path.to_string(),
+            result: "OK".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn rename(pid: u32, from: &str, to: &str) -> FsEvent {
+        FsEvent {
+            target_path: Some(to.to_string()),
+            ..event(pid, "rename", from)
+        }
+    }
+
+    fn detector() -> AtomicSaveDetector {
+        let roots = ["/".to_string(), "/System/Volumes/Data".to_string()];
+        AtomicSaveDetector::new(OperationClassifier::default(), &roots)
+    }
+
+    fn modified_path(change: Option<LogicalChange>) -> Option<(String, String, u32)> {
+        change.map(
+            |LogicalChange::Modified {
+                 path,
+                 via_temp,
+                 pid,
+                 ..
+             }| (path, via_temp, pid),
+        )
+    }
+
+    #[test]
+    fn test_temp_file_renamed_over_target() {
+        let mut detector = detector();
+        assert!(detector
+            .process(&event(7, "WrData[A]", "/src/foo.rs.tmp1234"))
+            .is_none());
+
+        // The rename is attributed to the process that wrote the temp file
+        let change = detector.process(&rename(8, "/src/foo.rs.tmp1234", "/src/foo.rs"));
+        assert_eq!(
+            modified_path(change),
+            Some((
+                "/src/foo.rs".to_string(),
+                "/src/foo.rs.tmp1234".to_string(),
+                7
+            ))
+        );
+
+        // Unseen temp names are still recognized by shape
+        let change = detector.process(&rename(8, "/src/.bar.rs.swp", "/src/bar.rs"));
+        assert_eq!(
+            modified_path(change),
+            Some(("/src/bar.rs".to_string(), "/src/.bar.rs.swp".to_string(), 8))
+        );
+
+        // Plain renames are not saves
+        assert!(detector
+            .process(&rename(8, "/src/a.rs", "/src/b.rs"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_backup_then_rewrite() {
+        let mut detector = detector();
+        assert!(detector
+            .process(&rename(3, "/src/foo.rs", "/src/foo.rs~"))
+            .is_none());
+        assert!(detector
+            .process(&event(4, "write", "/src/foo.rs"))
+            .is_none());
+
+        let change = detector.process(&event(3, "WrData[A]", "/src/foo.rs"));
+        assert_eq!(
+            modified_path(change),
+            Some(("/src/foo.rs".to_string(), "/src/foo.rs~".to_string(), 3))
+        );
+        assert!(detector
+            .process(&event(3, "unlink", "/src/foo.rs~"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_is_temp_path() {
+        for path in [
+            "/a/foo.rs.tmp",
+            "/a/foo.rs.tmp1234",
+            "/a/foo.rs.tmp.5f3a",
+            "/a/foo.rs.tmpXy12Ab",
+            "/a/.foo.rs.swp",
+            "/a/foo.rs~",
+            "/a/#foo.rs#",
+            "/a/foo.rs.sb-1a2b3c-XyZ",
+        ] {
+            assert!(is_temp_path(path), "{}", path);
+        }
+        for path in [
+            "/a/foo.rs",
+            "/a/tmp/foo.rs",
+            "/a/foo.tmpl",
+            "/a/foo.tmplate",
+            "/a/.gitignore",
+        ] {
+            assert!(!is_temp_path(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_relative_write_paths() {
+        let mut detector = detector();
+        // fs_usage prints the data write relative to the volume and the
+        // rename with its absolute path
+        let line = "21:35:08.701508    WrData[A]       D=0x1b3f3978  B=0x1000   /dev/disk3s5    Users/me/src/foo.rs.tmp1234      0.000303 W cargo.4242";
+        let write = crate::macos_impl::parse_fs_usage_line(line).unwrap();
+        assert_eq!(write.path, "Users/me/src/foo.rs.tmp1234");
+        assert!(detector.process(&write).is_none());
+
+        let change = detector.process(&rename(
+            9,
+            "/System/Volumes/Data/Users/me/src/foo.rs.tmp1234",
+            "/Users/me/src/foo.rs",
+        ));
+        assert_eq!(
+            modified_path(change),
+            Some((
+                "/Users/me/src/foo.rs".to_string(),
+                "/System/Volumes/Data/Users/me/src/foo.rs.tmp1234".to_string(),
+                4242
+            ))
+        );
+    }
+}
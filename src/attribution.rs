@@ -0,0 +1,624 @@
+use crate::process_info::ProcessInfoCache;
+use crate::FsEvent;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+
+/// Ancestors inspected above the event's process.
+const MAX_ANCESTRY_DEPTH: usize = 32;
+
+/// Broad category of whoever caused a file event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ActorKind {
+    HumanEditor,
+    AiAgent,
+    BuildTool,
+    System,
+    #[default]
+    Unknown,
+}
+
+/// The result of attributing an event: the matched rule's kind and name,
+/// and the process that matched it (the event's process or an ancestor).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Actor {
+    pub kind: ActorKind,
+    pub name: String,
+    pub pid: u32,
+}
+
+/// Describes the processes that belong to one actor.
+///
+/// Every non-empty criterion must match, or any of them with `match_any`,
+/// and a criterion matches if any of its entries does. `process_names`,
+/// `exe_paths`, `argv` and `parents` are globs; entries without wildcards
+/// match exactly, so short names like `cc` or `go` only match that tool.
+/// Both are case-sensitive, as `watch_process` patterns are. `env` entries are either `KEY` (set to
+/// anything) or `KEY=VALUE`. A rule without criteria never matches.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActorRule {
+    pub name: String,
+    pub kind: ActorKind,
+    /// Matched against the fs_usage process name and the basenames of the
+    /// executable and `argv[0]`.
+    pub process_names: Vec<String>,
+    pub exe_paths: Vec<String>,
+    /// Matched against each argument.
+    pub argv: Vec<String>,
+    /// Matched against the names of the direct parent process.
+    pub parents: Vec<String>,
+    pub env: Vec<String>,
+    /// Match when any non-empty criterion matches instead of all of them,
+    /// for actors recognisable in several independent ways.
+    pub match_any: bool,
+    /// Also match when any ancestor of the event's process matches, e.g. so
+    /// `cargo` run by an AI agent is attributed to the agent.
+    pub match_ancestors: bool,
+}
+
+impl ActorRule {
+    pub fn new(name: impl Into<String>, kind: ActorKind) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    pub fn process_name(mut self, pattern: impl Into<String>) -> Self {
+        self.process_names.push(pattern.into());
+        self
+    }
+
+    pub fn exe_path(mut self, pattern: impl Into<String>) -> Self {
+        self.exe_paths.push(pattern.into());
+        self
+    }
+
+    pub fn arg(mut self, pattern: impl Into<String>) -> Self {
+        self.argv.push(pattern.into());
+        self
+    }
+
+    pub fn parent(mut self, pattern: impl Into<String>) -> Self {
+        self.parents.push(pattern.into());
+        self
+    }
+
+    pub fn env(mut self, marker: impl Into<String>) -> Self {
+        self.env.push(marker.into());
+        self
+    }
+
+    pub fn match_any(mut self, enabled: bool) -> Self {
+        self.match_any = enabled;
+        self
+    }
+
+    pub fn match_ancestors(mut self, enabled: bool) -> Self {
+        self.match_ancestors = enabled;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Exact(String),
+    Glob(Pattern),
+}
+
+impl Matcher {
+    fn new(pattern: &str) -> Self {
+        // Invalid globs are treated as literal names
+        match Pattern::new(pattern) {
+            Ok(glob) if Pattern::escape(pattern) != pattern => Matcher::Glob(glob),
+            _ => Matcher::Exact(pattern.to_string()),
+        }
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Exact(name) => name == value,
+            Matcher::Glob(glob) => glob.matches(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompiledActorRule {
+    process_names: Vec<Matcher>,
+    exe_paths: Vec<Matcher>,
+    argv: Vec<Matcher>,
+    parents: Vec<Matcher>,
+}
+
+impl CompiledActorRule {
+    fn new(rule: &ActorRule) -> Self {
+        let compile = |patterns: &[String]| patterns.iter().map(|p| Matcher::new(p)).collect();
+        Self {
+            process_names: compile(&rule.process_names),
+            exe_paths: compile(&rule.exe_paths),
+            argv: compile(&rule.argv),
+            parents: compile(&rule.parents),
+        }
+    }
+}
+
+/// One process in the ancestry of an event.
+#[derive(Debug, Default)]
+struct Candidate {
+    pid: u32,
+    names: Vec<String>,
+    exe: Option<String>,
+    argv: Vec<String>,
+    env: Vec<String>,
+    parent_names: Vec<String>,
+}
+
+/// Tags events with the `Actor` that caused them by matching `ActorRule`s
+/// against the event's process and its ancestors.
+///
+/// Rules are checked in order and the first match wins; for each rule the
+/// event's process is tried first, then its ancestors if the rule has
+/// `match_ancestors`. `Attributor::default()` ships rules for common AI
+/// agents, editors, build tools and macOS system processes. The rule list
+/// (de)serializes as a plain JSON array so it can be loaded from a config
+/// file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<ActorRule>", into = "Vec<ActorRule>")]
+pub struct Attributor {
+    rules: Vec<ActorRule>,
+    compiled: Vec<CompiledActorRule>,
+}
+
+impl PartialEq for Attributor {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules == other.rules
+    }
+}
+
+impl From<Vec<ActorRule>> for Attributor {
+    fn from(rules: Vec<ActorRule>) -> Self {
+        let compiled = rules.iter().map(CompiledActorRule::new).collect();
+        Self { rules, compiled }
+    }
+}
+
+impl From<Attributor> for Vec<ActorRule> {
+    fn from(attributor: Attributor) -> Self {
+        attributor.rules
+    }
+}
+
+impl Attributor {
+    /// An attributor with no rules; every event is `ActorKind::Unknown`.
+    pub fn empty() -> Self {
+        Self::from(Vec::new())
+    }
+
+    pub fn rules(&self) -> &[ActorRule] {
+        &self.rules
+    }
+
+    /// Adds `rule` ahead of the existing rules, so it takes precedence.
+    pub fn add(&mut self, rule: ActorRule) -> &mut Self {
+        self.compiled.insert(0, CompiledActorRule::new(&rule));
+        self.rules.insert(0, rule);
+        self
+    }
+
+    /// Attributes `event`, looking up its process ancestry in `processes`.
+    /// Falls back to `ActorKind::Unknown` named after the event's process.
+    pub(crate) fn attribute(&self, event: &FsEvent, processes: &mut ProcessInfoCache) -> Actor {
+        let with_env = self.rules.iter().any(|rule| !rule.env.is_empty());
+        let chain = ancestry(event, processes, with_env);
+        self.attribute_chain(&chain).unwrap_or_else(|| Actor {
+            kind: ActorKind::Unknown,
+            name: event.process_name.clone(),
+            pid: event.pid,
+        })
+    }
+
+    fn attribute_chain(&self, chain: &[Candidate]) -> Option<Actor> {
+        self.rules
+            .iter()
+            .zip(&self.compiled)
+            .find_map(|(rule, compiled)| {
+                let depth = if rule.match_ancestors { chain.len() } else { 1 };
+                chain[..depth.min(chain.len())]
+                    .iter()
+                    .find(|candidate| rule_matches(rule, compiled, candidate))
+                    .map(|candidate| Actor {
+                        kind: rule.kind,
+                        name: rule.name.clone(),
+                        pid: candidate.pid,
+                    })
+            })
+    }
+}
+
+fn rule_matches(rule: &ActorRule, compiled: &CompiledActorRule, candidate: &Candidate) -> bool {
+    let any_glob = |matchers: &[Matcher], values: &[String]| {
+        matchers
+            .iter()
+            .any(|matcher| values.iter().any(|value| matcher.matches(value)))
+    };
+    let criteria = [
+        (
+            compiled.process_names.is_empty(),
+            any_glob(&compiled.process_names, &candidate.names),
+        ),
+        (
+            compiled.exe_paths.is_empty(),
+            any_glob(&compiled.exe_paths, candidate.exe.as_slice()),
+        ),
+        (
+            compiled.argv.is_empty(),
+            any_glob(&compiled.argv, &candidate.argv),
+        ),
+        (
+            compiled.parents.is_empty(),
+            any_glob(&compiled.parents, &candidate.parent_names),
+        ),
+        (
+            rule.env.is_empty(),
+            rule.env
+                .iter()
+                .any(|marker| env_matches(marker, &candidate.env)),
+        ),
+    ];
+
+    if rule.match_any {
+        criteria.iter().any(|(empty, matched)| !empty && *matched)
+    } else {
+        criteria.iter().any(|(empty, _)| !empty)
+            && criteria.iter().all(|(empty, matched)| *empty || *matched)
+    }
+}
+
+fn env_matches(marker: &str, env: &[String]) -> bool {
+    if marker.contains('=') {
+        env.iter().any(|entry| entry == marker)
+    } else {
+        env.iter()
+            .any(|entry| entry.split_once('=').is_some_and(|(key, _)| key == marker))
+    }
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The event's process followed by its ancestors, as far as they can be
+/// looked up.
+fn ancestry(event: &FsEvent, processes: &mut ProcessInfoCache, with_env: bool) -> Vec<Candidate> {
+    let mut chain: Vec<Candidate> = Vec::new();
+    let mut pid = event.pid;
+    while chain.len() <= MAX_ANCESTRY_DEPTH {
+        let Some(info) = processes.get(pid).cloned() else {
+            break;
+        };
+        let mut names: Vec<String> = info
+            .exe
+            .iter()
+            .chain(info.argv.first())
+            .map(|path| basename(path).to_string())
+            .collect();
+        if chain.is_empty() {
+            names.insert(0, event.process_name.clone());
+        }
+        let env = if with_env {
+            processes.environment(pid).unwrap_or_default().to_vec()
+        } else {
+            Vec::new()
+        };
+        chain.push(Candidate {
+            pid,
+            names,
+            exe: info.exe,
+            argv: info.argv,
+            env,
+            parent_names: Vec::new(),
+        });
+
+        if info.ppid == 0 || info.ppid == pid {
+            break;
+        }
+        pid = info.ppid;
+    }
+
+    if chain.is_empty() {
+        // The process already exited; only the fs_usage name is known
+        chain.push(Candidate {
+            pid: event.pid,
+            names: vec![event.process_name.clone()],
+            ..Default::default()
+        });
+    }
+    for i in 1..chain.len() {
+        chain[i - 1].parent_names = chain[i].names.clone();
+    }
+    chain
+}
+
+impl Default for Attributor {
+    fn default() -> Self {
+        use ActorKind::*;
+
+        let names = |rule: ActorRule, patterns: &[&str]| {
+            patterns
+                .iter()
+                .fold(rule, |rule, pattern| rule.process_name(*pattern))
+        };
+
+        Self::from(vec![
+            // AI agents, including the tools they run. Claude Code also marks
+            // the environment of what it runs, which survives detaching
+            ActorRule::new("Claude Code", AiAgent)
+                .process_name("claude")
+                .arg("*@anthropic-ai/claude-code*")
+                .env("CLAUDECODE=1")
+                .match_any(true)
+                .match_ancestors(true),
+            ActorRule::new("Codex", AiAgent)
+                .process_name("codex")
+                .arg("*@openai/codex*")
+                .match_any(true)
+                .match_ancestors(true),
+            ActorRule::new("Gemini CLI", AiAgent)
+                .process_name("gemini")
+                .match_ancestors(true),
+            ActorRule::new("Aider", AiAgent)
+                .process_name("aider")
+                .match_ancestors(true),
+            names(
+                ActorRule::new("Cursor", AiAgent),
+                &["Cursor", "Cursor Helper*"],
+            ),
+            names(
+                ActorRule::new("Windsurf", AiAgent),
+                &["Windsurf", "Windsurf Helper*"],
+            ),
+            // Editors, only when writing directly
+            names(ActorRule::new("Vim", HumanEditor), &["vim", "nvim", "vi"]),
+            names(ActorRule::new("Emacs", HumanEditor), &["emacs", "Emacs*"]),
+            names(
+                ActorRule::new("VS Code", HumanEditor),
+                &["Code", "Code Helper*"],
+            ),
+            names(
+                ActorRule::new("Sublime Text", HumanEditor),
+                &["Sublime Text", "subl", "sublime_text"],
+            ),
+            names(
+                ActorRule::new("Text editor", HumanEditor),
+                &[
+                    "nano", "hx", "helix", "zed", "Zed", "TextEdit", "BBEdit", "Xcode",
+                ],
+            ),
+            // Build tools and everything they spawn
+            names(
+                ActorRule::new("Build tool", BuildTool),
+                &[
+                    "cargo",
+                    "rustc",
+                    "make",
+                    "gmake",
+                    "cmake",
+                    "ninja",
+                    "bazel",
+                    "clang",
+                    "cc",
+                    "ld",
+                    "go",
+                    "npm",
+                    "yarn",
+                    "pnpm",
+                    "tsc",
+                    "gradle",
+                    "xcodebuild",
+                    "swift-build",
+                    "swiftc",
+                ],
+            )
+            .match_ancestors(true),
+            // macOS background services
+            names(
+                ActorRule::new("System", System),
+                &[
+                    "mds*",
+                    "mdworker*",
+                    "fseventsd",
+                    "launchd",
+                    "kernel_task",
+                    "com.apple.*",
+                    "Spotlight",
+                    "backupd",
+                    "cloudd",
+                    "bird",
+                    "distnoted",
+                ],
+            ),
+            ActorRule::new("System", System)
+                .exe_path("/System/*")
+                .exe_path("/usr/libexec/*")
+                .exe_path("/usr/sbin/*"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(pid: u32, name: &str, argv: &[&str], env: &[&str]) -> Candidate {
+        Candidate {
+            pid,
+            names: vec![name.to_string(), basename(argv[0]).to_string()],
+            exe: Some(format!("/usr/local/bin/{}", name)),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            env: env.iter().map(|entry| entry.to_string()).collect(),
+            parent_names: Vec::new(),
+        }
+    }
+
+    fn chain(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+        for i in 1..candidates.len() {
+            candidates[i - 1].parent_names = candidates[i].names.clone();
+        }
+        candidates
+    }
+
+    fn kind_and_pid(actor: Option<Actor>) -> Option<(ActorKind, u32)> {
+        actor.map(|actor| (actor.kind, actor.pid))
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let attributor = Attributor::default();
+        let login = candidate(1, "zsh", &["-zsh"], &[]);
+
+        let vim = chain(vec![candidate(10, "vim", &["vim", "a.rs"], &[]), login]);
+        assert_eq!(
+            kind_and_pid(attributor.attribute_chain(&vim)),
+            Some((ActorKind::HumanEditor, 10))
+        );
+
+        // cargo started by the agent is attributed to the agent
+        let agent_build = chain(vec![
+            candidate(30, "rustc", &["rustc"], &[]),
+            candidate(20, "cargo", &["cargo", "build"], &[]),
+            candidate(11, "node", &["claude"], &[]),
+            candidate(1, "zsh", &["-zsh"], &[]),
+        ]);
+        let actor = attributor.attribute_chain(&agent_build).unwrap();
+        assert_eq!((actor.kind, actor.pid), (ActorKind::AiAgent, 11));
+        assert_eq!(actor.name, "Claude Code");
+
+        let user_build = chain(vec![
+            candidate(30, "rustc", &["rustc"], &[]),
+            candidate(20, "cargo", &["cargo", "build"], &[]),
+            candidate(1, "zsh", &["-zsh"], &[]),
+        ]);
+        assert_eq!(
+            kind_and_pid(attributor.attribute_chain(&user_build)),
+            Some((ActorKind::BuildTool, 30))
+        );
+
+        // Detached from the agent, but it left its marker in the environment
+        let marked = chain(vec![candidate(
+            40,
+            "python3",
+            &["python3"],
+            &["CLAUDECODE=1"],
+        )]);
+        assert_eq!(
+            kind_and_pid(attributor.attribute_chain(&marked)),
+            Some((ActorKind::AiAgent, 40))
+        );
+
+        let unknown = chain(vec![candidate(50, "python3", &["python3"], &[])]);
+        assert_eq!(attributor.attribute_chain(&unknown), None);
+
+        // Plain names match exactly: `go` is a build tool, `Go` is not
+        let go = |name: &str| chain(vec![candidate(60, name, &[name], &[])]);
+        assert_eq!(
+            kind_and_pid(attributor.attribute_chain(&go("go"))),
+            Some((ActorKind::BuildTool, 60))
+        );
+        assert_eq!(attributor.attribute_chain(&go("Go")), None);
+        assert_eq!(attributor.attribute_chain(&go("gopls")), None);
+
+        // Plain names and globs follow the same case rule
+        let cursor = |name: &str| attributor.attribute_chain(&go(name)).map(|a| a.name);
+        assert_eq!(cursor("Cursor").as_deref(), Some("Cursor"));
+        assert_eq!(cursor("Cursor Helper (GPU)").as_deref(), Some("Cursor"));
+        assert_eq!(cursor("cursor"), None);
+        assert_eq!(cursor("cursor helper (GPU)"), None);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut attributor: Attributor = serde_json::from_str(
+            r#"[{"name": "Deploy", "kind": "BuildTool", "process_names": ["node"],
+                 "parents": ["deploy*"], "env": ["STAGE"]}]"#,
+        )
+        .unwrap();
+        attributor
+            .add(ActorRule::new("Editor", ActorKind::HumanEditor).exe_path("/Applications/*"));
+
+        let deploy = |stage: &[&str]| {
+            chain(vec![
+                candidate(2, "node", &["node", "index.js"], stage),
+                candidate(1, "deploy.sh", &["deploy.sh"], &[]),
+            ])
+        };
+        assert_eq!(
+            kind_and_pid(attributor.attribute_chain(&deploy(&["STAGE=prod"]))),
+            Some((ActorKind::BuildTool, 2))
+        );
+        // All criteria must match
+        assert_eq!(attributor.attribute_chain(&deploy(&[])), None);
+
+        let mut app = candidate(3, "Nova", &["Nova"], &[]);
+        app.exe = Some("/Applications/Nova.app/Contents/MacOS/Nova".to_string());
+        assert_eq!(
+            kind_and_pid(attributor.attribute_chain(&[app])),
+            Some((ActorKind::HumanEditor, 3))
+        );
+
+        let round_trip: Attributor =
+            serde_json::from_str(&serde_json::to_string(&attributor).unwrap()).unwrap();
+        assert_eq!(round_trip, attributor);
+    }
+
+    #[test]
+    fn test_attribute_spawned_process() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        // Wait for the child to exec `sleep`
+        let started = std::time::Instant::now();
+        while crate::ProcessInfo::lookup(child.id()).is_none_or(|info| info.argv != ["sleep", "30"])
+        {
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let test_exe = std::env::current_exe().unwrap().canonicalize().unwrap();
+        let tests = ActorRule::new("Tests", ActorKind::AiAgent)
+            .exe_path(test_exe.to_string_lossy())
+            .match_ancestors(true);
+        let attributor = Attributor::from(vec![
+            ActorRule::new("Sleeper", ActorKind::BuildTool).arg("30"),
+            tests.clone(),
+        ]);
+        let event = FsEvent {
+            process_name: "sleep".to_string(),
+            pid: child.id(),
+            ..Default::default()
+        };
+
+        let mut processes = ProcessInfoCache::new();
+        let actor = attributor.attribute(&event, &mut processes);
+        assert_eq!((actor.kind, actor.pid), (ActorKind::BuildTool, child.id()));
+        // Without the first rule, the test process is found as the parent
+        let actor = Attributor::from(vec![tests]).attribute(&event, &mut processes);
+        assert_eq!(
+            (actor.kind, actor.pid),
+            (ActorKind::AiAgent, std::process::id())
+        );
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let exited = FsEvent {
+            process_name: "gone".to_string(),
+            pid: u32::MAX,
+            ..Default::default()
+        };
+        let actor = attributor.attribute(&exited, &mut ProcessInfoCache::new());
+        assert_eq!(actor.kind, ActorKind::Unknown);
+        assert_eq!(actor.name, "gone");
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{Activity, FsEvent};
+use crossbeam_channel::{Receiver, SendTimeoutError, Sender, TrySendError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a blocked sender re-checks whether the monitor was stopped.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the reader thread does when a bounded event channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackpressurePolicy {
+    /// Wait for the consumer to make room. fs_usage output backs up in the
+    /// pipe meanwhile, and the kernel may drop trace events.
+    #[default]
+    Block,
+    /// Discard the event that did not fit.
+    DropNewest,
+    /// Discard the oldest queued event to make room.
+    DropOldest,
+    /// Hold overflow aside, keeping only the latest event per
+    /// (pid, path, operation), and deliver it once there is room again.
+    Coalesce,
+}
+
+/// Items that `BackpressurePolicy::Coalesce` can merge. Held-back items with
+/// the same key are replaced by the latest one; items without a key are
+/// never merged.
+pub(crate) trait CoalesceKey {
+    fn coalesce_key(&self) -> Option<(u32, String, String)>;
+}
+
+impl CoalesceKey for FsEvent {
+    fn coalesce_key(&self) -> Option<(u32, String, String)> {
+        Some((self.pid, self.path.clone(), self.operation.clone()))
+    }
+}
+
+impl CoalesceKey for Activity {
+    fn coalesce_key(&self) -> Option<(u32, String, String)> {
+        match self {
+            Activity::File(event) => event.coalesce_key(),
+            Activity::Monitor(_) => None,
+        }
+    }
+}
+
+/// Delivers events into a bounded channel (the monitor's own, or a
+/// subscriber's) according to a `BackpressurePolicy`, counting events that
+/// had to be dropped.
+pub(crate) struct PolicySender<T = FsEvent> {
+    sender: Sender<T>,
+    receiver: Receiver<T>,
+    policy: BackpressurePolicy,
+    pending: Vec<T>,
+    pending_index: HashMap<(u32, String, String), usize>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// The receiving side of the channel has been dropped.
+#[derive(Debug)]
+pub(crate) struct Disconnected;
+
+impl<T: CoalesceKey + Clone> PolicySender<T> {
+    pub(crate) fn new(
+        sender: Sender<T>,
+        receiver: Receiver<T>,
+        policy: BackpressurePolicy,
+        dropped: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            sender,
+            receiver,
+            policy,
+            pending: Vec::new(),
+            pending_index: HashMap::new(),
+            dropped,
+        }
+    }
+
+    /// Sends `event`, applying the backpressure policy if the channel is full.
+    /// `keep_waiting` is polled while blocked so a stopped monitor can exit.
+    pub(crate) fn send(
+        &mut self,
+        event: T,
+        keep_waiting: impl Fn() -> bool,
+    ) -> Result<(), Disconnected> {
+        if self.policy == BackpressurePolicy::Coalesce {
+            self.flush_pending()?;
+            if !self.pending.is_empty() {
+                self.coalesce(event);
+                return Ok(());
+            }
+        }
+
+        let event = match self.sender.try_send(event) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(_)) => return Err(Disconnected),
+            Err(TrySendError::Full(event)) => event,
+        };
+
+        match self.policy {
+            BackpressurePolicy::Block => {
+                let mut event = event;
+                loop {
+                    match self.sender.send_timeout(event, BLOCK_POLL_INTERVAL) {
+                        Ok(()) => return Ok(()),
+                        Err(SendTimeoutError::Disconnected(_)) => return Err(Disconnected),
+                        Err(SendTimeoutError::Timeout(returned)) => {
+                            if !keep_waiting() {
+                                self.record_drop();
+                                return Ok(());
+                            }
+                            event = returned;
+                        }
+                    }
+                }
+            }
+            BackpressurePolicy::DropNewest => {
+                self.record_drop();
+                Ok(())
+            }
+            BackpressurePolicy::DropOldest => {
+                if self.receiver.try_recv().is_ok() {
+                    self.record_drop();
+                }
+                match self.sender.try_send(event) {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Disconnected(_)) => Err(Disconnected),
+                    Err(TrySendError::Full(_)) => {
+                        self.record_drop();
+                        Ok(())
+                    }
+                }
+            }
+            BackpressurePolicy::Coalesce => {
+                self.coalesce(event);
+                Ok(())
+            }
+        }
+    }
+
+    /// Delivers coalesced overflow that now fits in the channel. Called
+    /// periodically so the last held-back events are not stuck until the
+    /// next event arrives.
+    pub(crate) fn flush(&mut self) -> Result<(), Disconnected> {
+        self.flush_pending()
+    }
+
+    fn coalesce(&mut self, event: T) {
+        let key = event.coalesce_key();
+        if let Some(&index) = key.as_ref().and_then(|key| self.pending_index.get(key)) {
+            self.pending[index] = event;
+            self.record_drop();
+            return;
+        }
+
+        // Bound the overflow buffer by the channel capacity
+        if self
+            .sender
+            .capacity()
+            .is_some_and(|cap| self.pending.len() >= cap)
+        {
+            self.record_drop();
+            return;
+        }
+
+        if let Some(key) = key {
+            self.pending_index.insert(key, self.pending.len());
+        }
+        self.pending.push(event);
+    }
+
+    fn flush_pending(&mut self) -> Result<(), Disconnected> {
+        let mut sent = 0;
+        for event in &self.pending {
+            match self.sender.try_send(event.clone()) {
+                Ok(()) => sent += 1,
+                Err(TrySendError::Full(_)) => break,
+                Err(TrySendError::Disconnected(_)) => return Err(Disconnected),
+            }
+        }
+
+        if sent > 0 {
+            self.pending.drain(..sent);
+            self.pending_index = self
+                .pending
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| Some((e.coalesce_key()?, i)))
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn record_drop(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Sender for status channels such as `monitor_events()` and `errors()`,
+/// which applications are not required to drain. Once the bounded channel is
+/// full the oldest item is discarded, so the queue keeps the latest ones.
+pub(crate) struct RingSender<T> {
+    sender: Sender<T>,
+    receiver: Receiver<T>,
+}
+
+impl<T> RingSender<T> {
+    pub(crate) fn new(sender: Sender<T>, receiver: Receiver<T>) -> Self {
+        Self { sender, receiver }
+    }
+
+    pub(crate) fn send(&self, item: T) {
+        let mut item = item;
+        loop {
+            match self.sender.try_send(item) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => return,
+                Err(TrySendError::Full(returned)) => {
+                    let _ = self.receiver.try_recv();
+                    item = returned;
+                }
+            }
+        }
+    }
+}
+
+impl<T> Clone for RingSender<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.sender.clone(), self.receiver.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::bounded;
+
+    fn event(path: &str) -> FsEvent {
+        FsEvent {
+            pid: 1,
+            operation: "write".to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn sender(policy: BackpressurePolicy) -> (PolicySender, Receiver<FsEvent>, Arc<AtomicU64>) {
+        let (tx, rx) = bounded(2);
+        let dropped = Arc::new(AtomicU64::new(0));
+        (
+            PolicySender::new(tx, rx.clone(), policy, dropped.clone()),
+            rx,
+            dropped,
+        )
+    }
+
+    fn paths(rx: &Receiver<FsEvent>) -> Vec<String> {
+        rx.try_iter().map(|e| e.path).collect()
+    }
+
+    #[test]
+    fn test_drop_newest_and_oldest() {
+        let (mut tx, rx, dropped) = sender(BackpressurePolicy::DropNewest);
+        for path in ["/a", "/b", "/c"] {
+            tx.send(event(path), || true).unwrap();
+        }
+        assert_eq!(paths(&rx), vec!["/a", "/b"]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+
+        let (mut tx, rx, dropped) = sender(BackpressurePolicy::DropOldest);
+        for path in ["/a", "/b", "/c"] {
+            tx.send(event(path), || true).unwrap();
+        }
+        assert_eq!(paths(&rx), vec!["/b", "/c"]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let (mut tx, rx, dropped) = sender(BackpressurePolicy::Coalesce);
+        for path in ["/a", "/b", "/c", "/d", "/c"] {
+            tx.send(event(path), || true).unwrap();
+        }
+        assert_eq!(paths(&rx), vec!["/a", "/b"]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+
+        // Overflow is delivered, in order, once there is room
+        tx.send(event("/e"), || true).unwrap();
+        assert_eq!(paths(&rx), vec!["/c", "/d"]);
+        tx.send(event("/f"), || true).unwrap();
+        assert_eq!(paths(&rx), vec!["/e", "/f"]);
+
+        // The trailing overflow is delivered without another send
+        for path in ["/g", "/h", "/i"] {
+            tx.send(event(path), || true).unwrap();
+        }
+        assert_eq!(paths(&rx), vec!["/g", "/h"]);
+        tx.flush().unwrap();
+        assert_eq!(paths(&rx), vec!["/i"]);
+        tx.flush().unwrap();
+        assert!(paths(&rx).is_empty());
+    }
+
+    #[test]
+    fn test_ring_sender_keeps_latest() {
+        let (tx, rx) = bounded(2);
+        let ring = RingSender::new(tx, rx.clone());
+        for path in ["/a", "/b", "/c"] {
+            ring.send(event(path));
+        }
+        assert_eq!(paths(&rx), vec!["/b", "/c"]);
+    }
+
+    #[test]
+    fn test_block_gives_up_when_stopped() {
+        let (mut tx, rx, dropped) = sender(BackpressurePolicy::Block);
+        tx.send(event("/a"), || true).unwrap();
+        tx.send(event("/b"), || true).unwrap();
+        tx.send(event("/c"), || false).unwrap();
+        assert_eq!(paths(&rx), vec!["/a", "/b"]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::FsEvent;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Groups `events` into batches of at most `max_len`, sending a batch once
+/// it is full or `max_wait` after its first event, whichever comes first.
+/// Returns when either side of the pipeline is dropped, after flushing any
+/// partial batch.
+pub(crate) fn run_batcher(
+    events: &Receiver<FsEvent>,
+    batches: &Sender<Vec<FsEvent>>,
+    max_len: usize,
+    max_wait: Duration,
+) {
+    let max_len = max_len.max(1);
+    let mut batch = Vec::with_capacity(max_len);
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let received = match deadline {
+            Some(deadline) => events.recv_deadline(deadline),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        let flush = match received {
+            Ok(event) => {
+                if batch.is_empty() {
+                    deadline = Some(Instant::now() + max_wait);
+                }
+                batch.push(event);
+                batch.len() >= max_len
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => {
+                if !batch.is_empty() {
+                    let _ = batches.send(batch);
+                }
+                return;
+            }
+        };
+
+        if flush {
+            deadline = None;
+            let full = std::mem::replace(&mut batch, Vec::with_capacity(max_len));
+            if batches.send(full).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use std::thread;
+
+    fn event(path: &str) -> FsEvent {
+        FsEvent {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn paths(batch: Vec<FsEvent>) -> Vec<String> {
+        batch.into_iter().map(|e| e.path).collect()
+    }
+
+    #[test]
+    fn test_batches_by_size_and_time() {
+        let (event_tx, event_rx) = unbounded();
+        let (batch_tx, batch_rx) = unbounded();
+        let batcher =
+            thread::spawn(move || run_batcher(&event_rx, &batch_tx, 2, Duration::from_millis(50)));
+
+        for path in ["/a", "/b", "/c"] {
+            event_tx.send(event(path)).unwrap();
+        }
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            paths(batch_rx.recv_timeout(timeout).unwrap()),
+            vec!["/a", "/b"]
+        );
+
+        // The partial batch is flushed after max_wait
+        let started = Instant::now();
+        assert_eq!(paths(batch_rx.recv_timeout(timeout).unwrap()), vec!["/c"]);
+        assert!(started.elapsed() < timeout);
+
+        event_tx.send(event("/d")).unwrap();
+        drop(event_tx);
+        assert_eq!(paths(batch_rx.recv_timeout(timeout).unwrap()), vec!["/d"]);
+        batcher.join().unwrap();
+        assert!(batch_rx.recv().is_err());
+    }
+}
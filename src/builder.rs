@@ -1,14 +1,26 @@
-use crate::{FsUsageConfig, FsUsageMonitor, OperationType};
-use anyhow::Result;
+use crate::error::{FsUsageError, Result};
+use crate::{
+    ActorRule, Attributor, BackpressurePolicy, ErrorHandler, EventFilter, EventHandler, FsEvent,
+    FsUsageConfig, FsUsageMonitor, OperationClassifier, OperationType, PathRule, ProcessPattern,
+    RateLimit, RestartPolicy, RuleMatching,
+};
+use std::sync::Arc;
+use std::time::Duration;
 
 pub struct FsUsageMonitorBuilder {
     config: FsUsageConfig,
+    filters: Vec<EventFilter>,
+    event_handlers: Vec<EventHandler>,
+    error_handlers: Vec<ErrorHandler>,
 }
 
 impl FsUsageMonitorBuilder {
     pub fn new() -> Self {
         Self {
             config: FsUsageConfig::default(),
+            filters: Vec::new(),
+            event_handlers: Vec::new(),
+            error_handlers: Vec::new(),
         }
     }
 
@@ -34,6 +46,34 @@ impl FsUsageMonitorBuilder {
         self
     }
 
+    /// Watch `pid` and every process descended from it, including children
+    /// spawned after the monitor starts, even short-lived ones that exit
+    /// before the next process table refresh. Exited descendants keep
+    /// matching for a few seconds so late events are still reported.
+    pub fn watch_pid_tree(mut self, pid: u32) -> Self {
+        self.config.watch_pid_trees.push(pid);
+        self
+    }
+
+    /// Only report events from processes whose name matches `process`.
+    /// Plain names match exactly, names containing `*`, `?` or `[` are
+    /// treated as globs; pass `ProcessPattern::regex(..)` for a regex. Both
+    /// are case-sensitive.
+    pub fn watch_process(mut self, process: impl Into<ProcessPattern>) -> Self {
+        self.config.watch_processes.push(process.into());
+        self
+    }
+
+    pub fn watch_processes(
+        mut self,
+        processes: impl IntoIterator<Item = impl Into<ProcessPattern>>,
+    ) -> Self {
+        self.config
+            .watch_processes
+            .extend(processes.into_iter().map(|p| p.into()));
+        self
+    }
+
     pub fn exclude_pid(mut self, pid: u32) -> Self {
         self.config.exclude_pids.push(pid);
         self
@@ -64,6 +104,23 @@ impl FsUsageMonitorBuilder {
         self
     }
 
+    /// Replaces the operation classification table used by this monitor.
+    pub fn operation_classifier(mut self, classifier: OperationClassifier) -> Self {
+        self.config.operation_classifier = classifier;
+        self
+    }
+
+    /// Adds `op_type` to the categories of an fs_usage operation name, e.g.
+    /// `.classify_operation("creat", OperationType::Write)`.
+    pub fn classify_operation(
+        mut self,
+        operation: impl Into<String>,
+        op_type: OperationType,
+    ) -> Self {
+        self.config.operation_classifier.add(operation, op_type);
+        self
+    }
+
     pub fn watch_writes_only(mut self) -> Self {
         self.config.operation_types = vec![
             OperationType::Write,
@@ -100,13 +157,171 @@ impl FsUsageMonitorBuilder {
         self
     }
 
+    /// Adds a rule reporting only the given operations (and optionally
+    /// processes) under a path glob. Rules are evaluated after the other
+    /// filters in the order they were added.
+    pub fn path_rule(mut self, rule: PathRule) -> Self {
+        self.config.path_rules.push(rule);
+        self
+    }
+
+    pub fn path_rules(mut self, rules: impl IntoIterator<Item = PathRule>) -> Self {
+        self.config.path_rules.extend(rules);
+        self
+    }
+
+    pub fn rule_matching(mut self, rule_matching: RuleMatching) -> Self {
+        self.config.rule_matching = rule_matching;
+        self
+    }
+
     pub fn exact_path_matching(mut self, enabled: bool) -> Self {
         self.config.exact_path_matching = enabled;
         self
     }
 
+    /// Adds a root that relative fs_usage paths are resolved against when
+    /// `exact_path_matching` is enabled. Defaults to `/` and
+    /// `/System/Volumes/Data`.
+    pub fn path_root(mut self, root: impl Into<String>) -> Self {
+        self.config.path_roots.push(root.into());
+        self
+    }
+
+    /// With `exact_path_matching`, also match relative paths that begin with a
+    /// trailing portion of a watch path. Off by default since it can match
+    /// unrelated directories that share a name.
+    pub fn match_relative_tails(mut self, enabled: bool) -> Self {
+        self.config.match_relative_tails = enabled;
+        self
+    }
+
+    /// Adds a predicate that every event must satisfy before it is sent.
+    /// Filters run on the reader thread after the built-in path, PID and
+    /// operation checks; multiple filters are combined with AND.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&FsEvent) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(Arc::new(filter));
+        self
+    }
+
+    /// Limits each process to `events_per_second` with bursts of up to `burst`
+    /// events. Dropped events are reported as `MonitorEvent::Suppressed` and
+    /// counted in `FsUsageMonitor::dropped_events()`. `build()` fails unless
+    /// the rate is positive and finite and `burst` is at least one.
+    pub fn rate_limit_per_process(mut self, events_per_second: f64, burst: u32) -> Self {
+        self.config.rate_limit.per_process = Some(RateLimit::new(events_per_second, burst));
+        self
+    }
+
+    /// Limits each path to `events_per_second` with bursts of up to `burst`
+    /// events, validated like `rate_limit_per_process`.
+    pub fn rate_limit_per_path(mut self, events_per_second: f64, burst: u32) -> Self {
+        self.config.rate_limit.per_path = Some(RateLimit::new(events_per_second, burst));
+        self
+    }
+
+    /// Keeps one in every `n` events from each process.
+    pub fn sample_every(mut self, n: u32) -> Self {
+        self.config.rate_limit.sample_every = n.max(1);
+        self
+    }
+
+    /// How often suppression summaries are emitted. Defaults to one second.
+    pub fn suppression_summary_interval(mut self, interval: Duration) -> Self {
+        self.config.rate_limit.summary_interval = interval;
+        self
+    }
+
+    /// Bounds the event channel to `capacity` events (at least one).
+    /// `backpressure` decides what happens when it is full; the default
+    /// blocks the reader.
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.channel_capacity = Some(capacity.max(1));
+        self
+    }
+
+    pub fn backpressure(mut self, policy: BackpressurePolicy) -> Self {
+        self.config.backpressure = policy;
+        self
+    }
+
+    /// Stops queueing events on `FsUsageMonitor::events()`, for monitors
+    /// consumed only through streams or subscriptions. Enabled by default.
+    pub fn events_channel(mut self, enabled: bool) -> Self {
+        self.config.events_channel = enabled;
+        self
+    }
+
+    /// Sends `MonitorEvent::ProcessStarted`/`ProcessExited` on
+    /// `FsUsageMonitor::monitor_events()` and `activity()`, from a poll of
+    /// the process table.
+    pub fn process_events(mut self, enabled: bool) -> Self {
+        self.config.process_events = enabled;
+        self
+    }
+
+    /// Attaches `ProcessInfo` (parent PID, executable path, command line, uid
+    /// and working directory) to each event. Lookups are cached per PID and
+    /// redone when the PID is reused by a new process.
+    pub fn enrich_process_info(mut self, enabled: bool) -> Self {
+        self.config.enrich_process_info = enabled;
+        self
+    }
+
+    /// Tags each event with the `Actor` that caused it, e.g.
+    /// `.attributor(Attributor::default())` for the built-in rules.
+    pub fn attributor(mut self, attributor: Attributor) -> Self {
+        self.config.attributor = Some(attributor);
+        self
+    }
+
+    /// Adds an attribution rule that takes precedence over the existing ones,
+    /// enabling attribution with the built-in rules if it was not enabled.
+    pub fn actor_rule(mut self, rule: ActorRule) -> Self {
+        self.config
+            .attributor
+            .get_or_insert_with(Attributor::default)
+            .add(rule);
+        self
+    }
+
+    /// Restarts fs_usage with backoff when it exits unexpectedly, sending
+    /// `MonitorEvent::MonitorRestarted`, or `MonitorFailed` once the policy
+    /// gives up, on `FsUsageMonitor::monitor_events()`.
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.config.restart_policy = Some(policy);
+        self
+    }
+
+    /// Calls `handler` on the reader thread for every event that passes the
+    /// filters. With a handler set, events are no longer queued on
+    /// `FsUsageMonitor::events()`.
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&FsEvent) + Send + Sync + 'static,
+    {
+        self.event_handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// Calls `handler` on the reader thread for each runtime error, e.g.
+    /// fs_usage exiting while the monitor is running. The same errors are
+    /// sent on `FsUsageMonitor::errors()`.
+    pub fn on_error<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&FsUsageError) + Send + Sync + 'static,
+    {
+        self.error_handlers.push(Arc::new(handler));
+        self
+    }
+
     pub fn build(self) -> Result<FsUsageMonitor> {
-        FsUsageMonitor::new(self.config)
+        Ok(FsUsageMonitor::new(self.config)?
+            .with_filters(self.filters)
+            .with_handlers(self.event_handlers, self.error_handlers))
     }
 }
 
@@ -0,0 +1,304 @@
+use crate::{FsEvent, OperationType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Maps fs_usage operation names to the `OperationType` categories they
+/// belong to.
+///
+/// `OperationClassifier::default()` contains the built-in table. Entries can
+/// be added, replaced or removed per monitor, and the table (de)serializes as
+/// a plain `{"operation": ["Category", ...]}` map so it can be loaded from a
+/// config file.
+///
+/// Operations carrying a bracketed suffix such as `WrData[A]` or `RdData[AT3]`
+/// fall back to the entry for the name before the `[`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OperationClassifier {
+    operations: BTreeMap<String, Vec<OperationType>>,
+}
+
+impl OperationClassifier {
+    /// A classifier with no entries, for building a table from scratch.
+    pub fn empty() -> Self {
+        Self {
+            operations: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the categories `operation` belongs to.
+    pub fn classify(&self, operation: &str) -> &[OperationType] {
+        if let Some(types) = self.operations.get(operation) {
+            return types;
+        }
+
+        operation
+            .split_once('[')
+            .and_then(|(base, _)| self.operations.get(base))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn matches(&self, op_type: &OperationType, operation: &str) -> bool {
+        *op_type == OperationType::All || self.classify(operation).contains(op_type)
+    }
+
+    /// Like `matches`, but also takes the decoded open mode into account: an
+    /// `open` counts as `Write` only with O_WRONLY/O_RDWR, O_APPEND or O_TRUNC,
+    /// as `Create` only with O_CREAT and as `Truncate` only with O_TRUNC.
+    pub fn matches_event(&self, op_type: &OperationType, event: &FsEvent) -> bool {
+        if self.matches(op_type, &event.operation) {
+            return true;
+        }
+
+        match event.open_flags {
+            Some(flags) if is_open_operation(&event.operation) => match op_type {
+                OperationType::Write => flags.write || flags.append || flags.truncate,
+                OperationType::Create => flags.create,
+                OperationType::Truncate => flags.truncate,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Adds `op_type` to the categories of `operation`.
+    pub fn add(&mut self, operation: impl Into<String>, op_type: OperationType) -> &mut Self {
+        let types = self.operations.entry(operation.into()).or_default();
+        if !types.contains(&op_type) {
+            types.push(op_type);
+        }
+        self
+    }
+
+    /// Replaces the categories of `operation`.
+    pub fn set(
+        &mut self,
+        operation: impl Into<String>,
+        types: impl IntoIterator<Item = OperationType>,
+    ) -> &mut Self {
+        self.operations
+            .insert(operation.into(), types.into_iter().collect());
+        self
+    }
+
+    /// Removes `operation` from the table so it no longer matches any category.
+    pub fn remove(&mut self, operation: &str) -> &mut Self {
+        self.operations.remove(operation);
+        self
+    }
+
+    pub(crate) fn builtin() -> &'static OperationClassifier {
+        static BUILTIN: OnceLock<OperationClassifier> = OnceLock::new();
+        BUILTIN.get_or_init(OperationClassifier::default)
+    }
+}
+
+pub(crate) fn is_open_operation(operation: &str) -> bool {
+    operation.starts_with("open")
+}
+
+/// Operations fs_usage prints with a source and a destination path.
+pub(crate) fn is_two_path_operation(operation: &str) -> bool {
+    matches!(
+        operation,
+        "rename"
+            | "renameat"
+            | "renamex_np"
+            | "renameatx_np"
+            | "link"
+            | "linkat"
+            | "symlink"
+            | "symlinkat"
+            | "clonefile"
+            | "clonefileat"
+            | "exchangedata"
+    )
+}
+
+impl Default for OperationClassifier {
+    fn default() -> Self {
+        use OperationType::*;
+
+        let table: &[(&str, &[OperationType])] = &[
+            ("read", &[Read]),
+            ("pread", &[Read]),
+            ("readv", &[Read]),
+            ("preadv", &[Read]),
+            ("RdData", &[Read]),
+            ("RdMeta", &[Read]),
+            ("write", &[Write]),
+            ("pwrite", &[Write]),
+            ("writev", &[Write]),
+            ("pwritev", &[Write]),
+            ("WrData", &[Write]),
+            ("WrMeta", &[Write]),
+            ("ftruncate", &[Write, Truncate]),
+            ("truncate", &[Write, Truncate]),
+            ("rename", &[Write, Move]),
+            ("renameat", &[Write, Move]),
+            ("renamex_np", &[Write, Move]),
+            ("renameatx_np", &[Write, Move]),
+            ("exchangedata", &[Write, Move]),
+            ("unlink", &[Write, Delete]),
+            ("unlinkat", &[Write, Delete]),
+            ("rmdir", &[Delete]),
+            ("remove", &[Delete]),
+            ("open", &[Open]),
+            ("open_nocancel", &[Open]),
+            ("openat", &[Open]),
+            ("openat_nocancel", &[Open]),
+            ("open_dprotected_np", &[Open]),
+            ("creat", &[Create]),
+            ("mkdir", &[Create]),
+            ("mkdirat", &[Create]),
+            ("mkfifo", &[Create]),
+            ("mknod", &[Create]),
+            ("clonefile", &[Create]),
+            ("clonefileat", &[Create]),
+            ("symlink", &[Create, Link]),
+            ("link", &[Create, Link]),
+            ("symlinkat", &[Create, Link]),
+            ("linkat", &[Create, Link]),
+            ("access", &[Access]),
+            ("faccessat", &[Access]),
+            ("stat", &[Access, Metadata]),
+            ("stat64", &[Access, Metadata]),
+            ("lstat", &[Access, Metadata]),
+            ("lstat64", &[Access, Metadata]),
+            ("fstat", &[Access, Metadata]),
+            ("fstat64", &[Access, Metadata]),
+            ("fstatat", &[Access, Metadata]),
+            ("fstatat64", &[Access, Metadata]),
+            ("getattrlist", &[Metadata]),
+            ("setattrlist", &[Metadata]),
+            ("fgetattrlist", &[Metadata]),
+            ("fsetattrlist", &[Metadata]),
+            ("getxattr", &[Metadata, Xattr]),
+            ("setxattr", &[Metadata, Xattr]),
+            ("listxattr", &[Metadata, Xattr]),
+            ("removexattr", &[Metadata, Xattr]),
+            ("fgetxattr", &[Metadata, Xattr]),
+            ("fsetxattr", &[Metadata, Xattr]),
+            ("flistxattr", &[Metadata, Xattr]),
+            ("fremovexattr", &[Metadata, Xattr]),
+            ("chmod", &[Chmod]),
+            ("fchmod", &[Chmod]),
+            ("fchmodat", &[Chmod]),
+            ("chmod_extended", &[Write, Chmod]),
+            ("fchmod_extended", &[Write, Chmod]),
+            ("execve", &[Exec]),
+            ("posix_spawn", &[Exec]),
+        ];
+
+        Self {
+            operations: table
+                .iter()
+                .map(|(operation, types)| (operation.to_string(), types.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpenFlags;
+
+    #[test]
+    fn test_default_table() {
+        let classifier = OperationClassifier::default();
+        assert!(classifier.matches(&OperationType::Write, "WrData[AT3]"));
+        assert!(classifier.matches(&OperationType::Read, "RdData[A]"));
+        assert!(classifier.matches(&OperationType::Truncate, "ftruncate"));
+        assert!(classifier.matches(&OperationType::Xattr, "setxattr"));
+        assert!(classifier.matches(&OperationType::Exec, "execve"));
+        assert!(classifier.matches(&OperationType::Link, "symlink"));
+        assert!(classifier.matches(&OperationType::Open, "openat"));
+        assert!(!classifier.matches(&OperationType::Create, "openat"));
+        assert!(classifier.matches(&OperationType::All, "anything"));
+        assert!(classifier.classify("unknown_op").is_empty());
+        assert!(classifier.matches(&OperationType::Write, "truncate"));
+
+        // *at and f* variants classify like their base syscalls
+        for (variant, base) in [
+            ("renameat", "rename"),
+            ("renameatx_np", "rename"),
+            ("unlinkat", "unlink"),
+            ("mkdirat", "mkdir"),
+            ("symlinkat", "symlink"),
+            ("linkat", "link"),
+            ("faccessat", "access"),
+            ("fstatat", "stat"),
+            ("fgetattrlist", "getattrlist"),
+            ("fgetxattr", "getxattr"),
+            ("fremovexattr", "removexattr"),
+            ("fchmod", "chmod"),
+            ("fchmodat", "chmod"),
+            ("ftruncate", "truncate"),
+        ] {
+            assert_eq!(
+                classifier.classify(variant),
+                classifier.classify(base),
+                "{variant}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_flags() {
+        let classifier = OperationClassifier::default();
+        let open = |mode: &str| FsEvent {
+            operation: "open".to_string(),
+            path: "/tmp/file".to_string(),
+            open_flags: OpenFlags::parse(mode),
+            ..Default::default()
+        };
+
+        let read_only = open("(R_____)");
+        assert!(classifier.matches_event(&OperationType::Open, &read_only));
+        assert!(!classifier.matches_event(&OperationType::Write, &read_only));
+        assert!(!classifier.matches_event(&OperationType::Create, &read_only));
+
+        let create = open("(_WC_T_)");
+        assert!(classifier.matches_event(&OperationType::Write, &create));
+        assert!(classifier.matches_event(&OperationType::Create, &create));
+        assert!(classifier.matches_event(&OperationType::Truncate, &create));
+
+        let unknown = open("");
+        assert!(!classifier.matches_event(&OperationType::Write, &unknown));
+        assert!(!classifier.matches_event(&OperationType::Create, &unknown));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut classifier = OperationClassifier::default();
+        assert!(!classifier.matches(&OperationType::Write, "creat"));
+
+        classifier
+            .add("creat", OperationType::Write)
+            .add("mkdir", OperationType::Write)
+            .set("open", [OperationType::Write])
+            .remove("rename");
+
+        assert!(classifier.matches(&OperationType::Write, "creat"));
+        assert!(classifier.matches(&OperationType::Create, "creat"));
+        assert!(classifier.matches(&OperationType::Write, "mkdir"));
+        assert!(!classifier.matches(&OperationType::Create, "open"));
+        assert!(!classifier.matches(&OperationType::Move, "rename"));
+    }
+
+    #[test]
+    fn test_load_from_json() {
+        let json = r#"{"open": ["Write", "Open"], "WrData": ["Write"]}"#;
+        let classifier: OperationClassifier = serde_json::from_str(json).unwrap();
+        assert!(classifier.matches(&OperationType::Write, "open"));
+        assert!(classifier.matches(&OperationType::Write, "WrData[A]"));
+        assert!(!classifier.matches(&OperationType::Read, "read"));
+
+        let round_trip: OperationClassifier =
+            serde_json::from_str(&serde_json::to_string(&classifier).unwrap()).unwrap();
+        assert_eq!(round_trip, classifier);
+    }
+}
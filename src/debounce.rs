@@ -0,0 +1,206 @@
+use crate::FsEvent;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+/// A burst of activity by one process on one path, summarized by
+/// `FsUsageMonitor::debounce()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub process_name: String,
+    pub pid: u32,
+    /// Distinct fs_usage operation names seen during the burst.
+    pub operations: BTreeSet<String>,
+    pub first_timestamp: String,
+    pub last_timestamp: String,
+    /// Sum of the `B=` byte counts of the events in the burst.
+    pub bytes: u64,
+    pub event_count: usize,
+}
+
+impl FileChange {
+    fn new(event: &FsEvent) -> Self {
+        Self {
+            path: event.path.clone(),
+            process_name: event.process_name.clone(),
+            pid: event.pid,
+            operations: BTreeSet::new(),
+            first_timestamp: event.timestamp.clone(),
+            last_timestamp: event.timestamp.clone(),
+            bytes: 0,
+            event_count: 0,
+        }
+    }
+
+    fn add(&mut self, event: &FsEvent) {
+        self.operations.insert(event.operation.clone());
+        self.last_timestamp = event.timestamp.clone();
+        self.bytes += event.bytes.unwrap_or(0);
+        self.event_count += 1;
+    }
+}
+
+struct Pending {
+    change: FileChange,
+    sequence: u64,
+    last_seen: Instant,
+}
+
+/// Coalesces events per (path, pid) until that pair has been quiet for
+/// `quiet`.
+pub(crate) struct Debouncer {
+    quiet: Duration,
+    pending: HashMap<(String, u32), Pending>,
+    next_sequence: u64,
+}
+
+impl Debouncer {
+    pub(crate) fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            pending: HashMap::new(),
+            next_sequence: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: &FsEvent, now: Instant) {
+        let key = (event.path.clone(), event.pid);
+        let pending = self.pending.entry(key).or_insert_with(|| {
+            self.next_sequence += 1;
+            Pending {
+                change: FileChange::new(event),
+                sequence: self.next_sequence,
+                last_seen: now,
+            }
+        });
+        pending.change.add(event);
+        pending.last_seen = now;
+    }
+
+    /// When the next pending change becomes due, if any.
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.pending
+            .values()
+            .map(|pending| pending.last_seen + self.quiet)
+            .min()
+    }
+
+    /// Removes and returns changes that have been quiet long enough, in the
+    /// order they started.
+    pub(crate) fn take_due(&mut self, now: Instant) -> Vec<FileChange> {
+        let quiet = self.quiet;
+        self.take(|pending| now.saturating_duration_since(pending.last_seen) >= quiet)
+    }
+
+    pub(crate) fn take_all(&mut self) -> Vec<FileChange> {
+        self.take(|_| true)
+    }
+
+    fn take(&mut self, due: impl Fn(&Pending) -> bool) -> Vec<FileChange> {
+        let keys: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| due(pending))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let mut due: Vec<Pending> = keys
+            .iter()
+            .filter_map(|key| self.pending.remove(key))
+            .collect();
+        due.sort_by_key(|pending| pending.sequence);
+        due.into_iter().map(|pending| pending.change).collect()
+    }
+}
+
+/// Feeds `events` through a `Debouncer`, sending each change once it is due.
+/// Returns when either side is dropped, after flushing pending changes.
+pub(crate) fn run_debouncer(
+    events: &Receiver<FsEvent>,
+    changes: &Sender<FileChange>,
+    quiet: Duration,
+) {
+    let mut debouncer = Debouncer::new(quiet);
+    loop {
+        let received = match debouncer.next_deadline() {
+            Some(deadline) => events.recv_deadline(deadline),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        let due = match received {
+            Ok(event) => {
+                debouncer.push(&event, Instant::now());
+                debouncer.take_due(Instant::now())
+            }
+            Err(RecvTimeoutError::Timeout) => debouncer.take_due(Instant::now()),
+            Err(RecvTimeoutError::Disconnected) => {
+                for change in debouncer.take_all() {
+                    let _ = changes.send(change);
+                }
+                return;
+            }
+        };
+
+        for change in due {
+            if changes.send(change).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(pid: u32, operation: &str, path: &str, timestamp: &str, bytes: u64) -> FsEvent {
+        FsEvent {
+            timestamp: timestamp.to_string(),
+            process_name: "vim".to_string(),
+            pid,
+            operation: operation.to_string(),
+            path: path.to_string(),
+            result: "OK".to_string(),
+            bytes: Some(bytes),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_coalesces_bursts_per_path_and_process() {
+        let start = Instant::now();
+        let quiet = Duration::from_millis(100);
+        let mut debouncer = Debouncer::new(quiet);
+
+        debouncer.push(&event(1, "open", "/a.rs", "10:00:00.000", 0), start);
+        debouncer.push(&event(1, "WrData[A]", "/a.rs", "10:00:00.001", 4096), start);
+        debouncer.push(&event(2, "read", "/a.rs", "10:00:00.002", 10), start);
+        let later = start + Duration::from_millis(50);
+        debouncer.push(&event(1, "WrData[A]", "/a.rs", "10:00:00.050", 100), later);
+        debouncer.push(&event(1, "close", "/a.rs", "10:00:00.051", 0), later);
+
+        assert_eq!(debouncer.next_deadline(), Some(start + quiet));
+        assert!(debouncer.take_due(later).is_empty());
+
+        // pid 2 went quiet first; pid 1's burst was extended by the later writes
+        let due = debouncer.take_due(start + quiet);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].pid, 2);
+
+        let due = debouncer.take_due(later + quiet);
+        assert_eq!(due.len(), 1);
+        let change = &due[0];
+        assert_eq!(change.path, "/a.rs");
+        assert_eq!(change.event_count, 4);
+        assert_eq!(change.bytes, 4196);
+        assert_eq!(change.first_timestamp, "10:00:00.000");
+        assert_eq!(change.last_timestamp, "10:00:00.051");
+        assert_eq!(
+            change.operations.iter().collect::<Vec<_>>(),
+            vec!["WrData[A]", "close", "open"]
+        );
+        assert_eq!(debouncer.next_deadline(), None);
+    }
+}
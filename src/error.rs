@@ -0,0 +1,155 @@
+use std::fmt;
+use std::io;
+use std::process::ExitStatus;
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub(crate) type Result<T> = std::result::Result<T, FsUsageError>;
+
+/// Errors returned by the monitor's methods and reported at runtime on
+/// `FsUsageMonitor::errors()` and to `on_error` handlers.
+#[derive(Debug)]
+pub enum FsUsageError {
+    /// fs_usage refused to run because the process is not root.
+    NotRoot,
+    /// No `fs_usage` binary was found on `PATH`.
+    BinaryMissing,
+    /// fs_usage could not be started for another reason.
+    SpawnFailed(io::Error),
+    /// fs_usage exited while the monitor was running. `stderr` holds the end
+    /// of its error output.
+    ChildExited {
+        status: Option<ExitStatus>,
+        stderr: String,
+    },
+    /// A line of fs_usage output looked like a trace record but could not be
+    /// parsed. The line is skipped.
+    ParseError { line: String },
+    /// Another process holds the kernel trace facility, so fs_usage fails
+    /// with "Resource busy". `holder` is the (pid, name) of that process, when
+    /// it could be identified.
+    KtraceBusy { holder: Option<(u32, String)> },
+    /// The event channel was closed.
+    ChannelClosed,
+    /// `start()` was called on a running monitor.
+    AlreadyRunning,
+    /// The call is not possible in the monitor's current state or context.
+    InvalidState(String),
+    /// A glob or regular expression in the configuration failed to compile.
+    InvalidPattern { pattern: String, message: String },
+    /// Any other I/O failure, e.g. reading fs_usage's output or stopping it.
+    Io { context: String, source: io::Error },
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+impl FsUsageError {
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+        FsUsageError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Classifies a failure to spawn fs_usage.
+    pub(crate) fn spawn_failed(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => FsUsageError::BinaryMissing,
+            _ => FsUsageError::SpawnFailed(error),
+        }
+    }
+
+    /// Classifies fs_usage exiting, recognizing its complaints about missing
+    /// privileges and a busy ktrace session. The session's holder is not
+    /// known here.
+    pub(crate) fn child_exited(status: Option<ExitStatus>, stderr: String) -> Self {
+        if stderr.contains("must be run as root") {
+            FsUsageError::NotRoot
+        } else if stderr.contains("Resource busy") {
+            FsUsageError::KtraceBusy { holder: None }
+        } else {
+            FsUsageError::ChildExited { status, stderr }
+        }
+    }
+}
+
+impl fmt::Display for FsUsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsUsageError::NotRoot => write!(f, "fs_usage must be run as root"),
+            FsUsageError::BinaryMissing => {
+                write!(f, "Failed to start fs_usage: binary not found on PATH")
+            }
+            FsUsageError::SpawnFailed(e) => write!(f, "Failed to start fs_usage: {}", e),
+            FsUsageError::ChildExited { status, stderr } => {
+                match status.and_then(|status| status.code()) {
+                    Some(code) => write!(f, "fs_usage exited with code {}", code)?,
+                    None => write!(f, "fs_usage exited unexpectedly")?,
+                }
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {}", stderr),
+                }
+            }
+            FsUsageError::ParseError { line } => {
+                write!(f, "Failed to parse fs_usage output: {}", line)
+            }
+            FsUsageError::KtraceBusy { holder } => {
+                write!(f, "Another ktrace session is active")?;
+                match holder {
+                    Some((pid, process_name)) => write!(f, " ({}, pid {})", process_name, pid),
+                    None => Ok(()),
+                }
+            }
+            FsUsageError::ChannelClosed => write!(f, "Event channel closed"),
+            FsUsageError::AlreadyRunning => write!(f, "Monitor is already running"),
+            FsUsageError::InvalidState(message) => write!(f, "{}", message),
+            FsUsageError::InvalidPattern { pattern, message } => {
+                write!(f, "Invalid pattern '{}': {}", pattern, message)
+            }
+            FsUsageError::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for FsUsageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FsUsageError::SpawnFailed(e) | FsUsageError::Io { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classification() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
+        assert!(matches!(
+            FsUsageError::spawn_failed(missing),
+            FsUsageError::BinaryMissing
+        ));
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied");
+        assert!(matches!(
+            FsUsageError::spawn_failed(denied),
+            FsUsageError::SpawnFailed(_)
+        ));
+
+        let not_root = FsUsageError::child_exited(
+            None,
+            "fs_usage: this program must be run as root\n".to_string(),
+        );
+        assert!(matches!(not_root, FsUsageError::NotRoot));
+
+        let busy = FsUsageError::child_exited(None, "ktrace_start: Resource busy\n".to_string());
+        assert!(matches!(busy, FsUsageError::KtraceBusy { holder: None }));
+        assert_eq!(busy.to_string(), "Another ktrace session is active");
+
+        let crashed = FsUsageError::child_exited(None, "Segmentation fault\n".to_string());
+        assert_eq!(
+            crashed.to_string(),
+            "fs_usage exited unexpectedly: Segmentation fault"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{FsEvent, OpenFlags};
+use std::collections::HashMap;
+
+/// Upper bound on tracked descriptors. Processes that exit without closing
+/// their files would otherwise grow the table forever.
+const MAX_OPEN_FILES: usize = 65_536;
+
+/// Per-(pid, fd) record of files opened while the monitor is running.
+///
+/// fs_usage prints plain `write`/`pwrite` calls with only an `F=` descriptor,
+/// so the reader uses this table to attach the path (and the open mode) from
+/// the matching `open`. When fs_usage did not report the open mode, a write
+/// on the descriptor is taken as evidence that it was opened for writing.
+#[derive(Default)]
+pub(crate) struct FdTable {
+    files: HashMap<(u32, u32), OpenFile>,
+}
+
+struct OpenFile {
+    path: String,
+    flags: Option<OpenFlags>,
+}
+
+impl FdTable {
+    /// Records opens and closes from `event` and fills in `path` and
+    /// `open_flags` for descriptor-only writes on a known file.
+    pub(crate) fn resolve(&mut self, event: &mut FsEvent) {
+        let Some(fd) = event.fd else {
+            return;
+        };
+        let key = (event.pid, fd);
+
+        if crate::classifier::is_open_operation(&event.operation) {
+            if !event.path.is_empty() && event.result == "OK" {
+                if self.files.len() >= MAX_OPEN_FILES {
+                    self.files.clear();
+                }
+                self.files.insert(
+                    key,
+                    OpenFile {
+                        path: event.path.clone(),
+                        flags: event.open_flags,
+                    },
+                );
+            }
+            return;
+        }
+
+        if matches!(event.operation.as_str(), "close" | "close_nocancel") {
+            self.files.remove(&key);
+            return;
+        }
+
+        if event.path.is_empty() && is_fd_write(&event.operation) {
+            if let Some(file) = self.files.get_mut(&key) {
+                let flags = file.flags.get_or_insert(OpenFlags {
+                    write: true,
+                    ..Default::default()
+                });
+                event.path = file.path.clone();
+                event.open_flags = Some(*flags);
+            }
+        }
+    }
+}
+
+fn is_fd_write(operation: &str) -> bool {
+    matches!(
+        operation.trim_end_matches("_nocancel"),
+        "write" | "pwrite" | "writev" | "pwritev"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(operation: &str, fd: u32, path: &str, open_flags: Option<OpenFlags>) -> FsEvent {
+        FsEvent {
+            operation: operation.to_string(),
+            pid: 42,
+            fd: Some(fd),
+            path: path.to_string(),
+            result: "OK".to_string(),
+            open_flags,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolves_writes_to_opened_path() {
+        let mut table = FdTable::default();
+        let read_write = OpenFlags::parse("(RW____)");
+
+        table.resolve(&mut event("open", 5, "/repo/a.rs", read_write));
+        table.resolve(&mut event("open", 6, "/repo/b.rs", None));
+
+        let mut write = event("write", 5, "", None);
+        table.resolve(&mut write);
+        assert_eq!(write.path, "/repo/a.rs");
+        assert_eq!(write.open_flags, read_write);
+
+        // Unknown open mode is inferred from the write
+        let mut write = event("write_nocancel", 6, "", None);
+        table.resolve(&mut write);
+        assert_eq!(write.path, "/repo/b.rs");
+        assert!(write.open_flags.unwrap().write);
+
+        table.resolve(&mut event("close", 5, "", None));
+        let mut write = event("pwrite", 5, "", None);
+        table.resolve(&mut write);
+        assert!(write.path.is_empty());
+
+        // Reads are left alone
+        let mut read = event("read", 6, "", None);
+        table.resolve(&mut read);
+        assert!(read.path.is_empty());
+    }
+}
@@ -1,6 +1,42 @@
 // Only compile the actual implementation on macOS
 #[cfg(target_os = "macos")]
+mod atomic_save;
+// Attribution and the process lookup cache are only driven by the macOS monitor
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod attribution;
+#[cfg(target_os = "macos")]
+mod backpressure;
+#[cfg(target_os = "macos")]
+mod batch;
+#[cfg(target_os = "macos")]
 mod builder;
+#[cfg(target_os = "macos")]
+mod classifier;
+#[cfg(target_os = "macos")]
+mod debounce;
+mod error;
+#[cfg(target_os = "macos")]
+mod fd_table;
+#[cfg(target_os = "macos")]
+mod path_matcher;
+#[cfg(target_os = "macos")]
+mod preflight;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod process_info;
+#[cfg(target_os = "macos")]
+mod process_tree;
+#[cfg(target_os = "macos")]
+mod process_watch;
+#[cfg(target_os = "macos")]
+mod rate_limit;
+#[cfg(target_os = "macos")]
+mod session;
+#[cfg(target_os = "macos")]
+mod stream;
+#[cfg(target_os = "macos")]
+mod subscription;
+#[cfg(target_os = "macos")]
+mod supervisor;
 
 // Provide a stub module for non-macOS platforms
 #[cfg(not(target_os = "macos"))]
@@ -14,12 +50,15 @@ mod builder {
     }
 }
 
+pub use attribution::{Actor, ActorKind, ActorRule, Attributor};
 pub use builder::FsUsageMonitorBuilder;
+pub use error::FsUsageError;
+pub use process_info::ProcessInfo;
 
 use serde::{Deserialize, Serialize};
 
 // FsEvent is available on all platforms for API compatibility
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FsEvent {
     pub timestamp: String,
     pub process_name: String,
@@ -27,9 +66,109 @@ pub struct FsEvent {
     pub operation: String,
     pub path: String,
     pub result: String,
+    /// File descriptor from the `F=` column, when fs_usage reports one.
+    #[serde(default)]
+    pub fd: Option<u32>,
+    /// Open mode for `open` family operations. Decoded from fs_usage's mode
+    /// column, or inferred as write-only for fd-based writes to a file whose
+    /// open mode was not reported. Inferred flags are only attached to those
+    /// later writes: the `open` itself has already been filtered by then, so
+    /// an open whose mode fs_usage did not print never counts as a write.
+    #[serde(default)]
+    pub open_flags: Option<OpenFlags>,
+    /// Byte count from the `B=` column, when fs_usage reports one.
+    #[serde(default)]
+    pub bytes: Option<u64>,
+    /// Destination of two-path operations such as `rename`, `link`, `symlink`,
+    /// `clonefile` and `exchangedata`; `path` holds the source.
+    #[serde(default)]
+    pub target_path: Option<String>,
+    /// Details of the process, when `enrich_process_info` is enabled and
+    /// the process could still be inspected.
+    #[serde(default)]
+    pub process_info: Option<ProcessInfo>,
+    /// Who caused the event, when an `Attributor` is configured.
+    #[serde(default)]
+    pub actor: Option<Actor>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Monitor status and bookkeeping events, delivered separately from file
+/// events on `FsUsageMonitor::monitor_events()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MonitorEvent {
+    /// `count` events from this process were dropped by rate limiting or
+    /// sampling since the previous summary.
+    Suppressed {
+        pid: u32,
+        process_name: String,
+        count: u64,
+    },
+    /// A process appeared in the process table. Only sent with
+    /// `process_events` enabled.
+    ProcessStarted {
+        pid: u32,
+        ppid: u32,
+        name: String,
+        argv: Vec<String>,
+    },
+    /// A process exited, or its PID was reused by a new process. Detected
+    /// when the PID leaves the process table, so file events it caused just
+    /// before exiting may arrive after this.
+    ProcessExited { pid: u32 },
+    /// fs_usage exited unexpectedly and was started again under the
+    /// monitor's `RestartPolicy`. `attempt` counts consecutive restarts since
+    /// the last stable run.
+    MonitorRestarted { attempt: u32 },
+    /// fs_usage exited unexpectedly and the `RestartPolicy` gave up; the
+    /// monitor has stopped.
+    MonitorFailed { attempts: u32, error: String },
+}
+
+/// File and monitor events on one channel, from `FsUsageMonitor::activity()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Activity {
+    File(Box<FsEvent>),
+    Monitor(MonitorEvent),
+}
+
+/// Access mode of an `open` call as printed by fs_usage, e.g. `(RWC_T__)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OpenFlags {
+    pub read: bool,
+    pub write: bool,
+    pub create: bool,
+    pub append: bool,
+    pub truncate: bool,
+    pub exclusive: bool,
+}
+
+impl OpenFlags {
+    /// Parses fs_usage's mode column. Returns `None` unless the input is a
+    /// parenthesised run of mode letters and underscores.
+    pub fn parse(mode: &str) -> Option<Self> {
+        let mode = mode.strip_prefix('(')?.strip_suffix(')')?;
+        if mode.is_empty() || !mode.chars().all(|c| c == '_' || c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        Some(Self {
+            read: mode.contains('R'),
+            write: mode.contains('W'),
+            create: mode.contains('C'),
+            append: mode.contains('A'),
+            truncate: mode.contains('T'),
+            exclusive: mode.contains('E'),
+        })
+    }
+
+    /// Whether the open can modify the file (O_WRONLY/O_RDWR, O_CREAT,
+    /// O_APPEND or O_TRUNC).
+    pub fn is_write(&self) -> bool {
+        self.write || self.create || self.append || self.truncate
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OperationType {
     Read,
     Write,
@@ -39,79 +178,146 @@ pub enum OperationType {
     Access,
     Metadata,
     Chmod,
+    Truncate,
+    Xattr,
+    Exec,
+    Link,
+    Open,
     All,
 }
 
+/// Process name matcher used by `watch_processes`.
+///
+/// Converting from a string picks `Glob` when the string contains glob
+/// metacharacters (`*`, `?`, `[`) and `Exact` otherwise; use
+/// `ProcessPattern::Regex` explicitly for regular expressions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessPattern {
+    Exact(String),
+    Glob(String),
+    Regex(String),
+}
+
+impl ProcessPattern {
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        ProcessPattern::Regex(pattern.into())
+    }
+}
+
+impl From<&str> for ProcessPattern {
+    fn from(pattern: &str) -> Self {
+        if pattern.contains(['*', '?', '[']) {
+            ProcessPattern::Glob(pattern.to_string())
+        } else {
+            ProcessPattern::Exact(pattern.to_string())
+        }
+    }
+}
+
+impl From<String> for ProcessPattern {
+    fn from(pattern: String) -> Self {
+        ProcessPattern::from(pattern.as_str())
+    }
+}
+
+/// Pairs a path glob with the operations (and optionally processes) that
+/// should be reported under it, e.g. reads under `/Users/me/.ssh/**` but
+/// writes anywhere under `/repo/**`.
+///
+/// An empty `processes` list matches every process. An empty `operations`
+/// list matches no operation, which together with `RuleMatching::FirstMatch`
+/// can be used to drop everything under a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathRule {
+    pub path: String,
+    pub operations: Vec<OperationType>,
+    pub processes: Vec<ProcessPattern>,
+}
+
+impl PathRule {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            operations: vec![OperationType::All],
+            processes: vec![],
+        }
+    }
+
+    pub fn operations(mut self, operations: impl IntoIterator<Item = OperationType>) -> Self {
+        self.operations = operations.into_iter().collect();
+        self
+    }
+
+    pub fn process(mut self, process: impl Into<ProcessPattern>) -> Self {
+        self.processes.push(process.into());
+        self
+    }
+}
+
+/// How `path_rules` are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RuleMatching {
+    /// The first rule whose path and process match decides whether the
+    /// event's operation is reported.
+    #[default]
+    FirstMatch,
+    /// The event is reported if any rule matches its path, process and
+    /// operation.
+    AnyMatch,
+}
+
+/// Predicate run on the reader thread before an event is sent to the channel.
+/// Returning `false` drops the event.
+pub type EventFilter = std::sync::Arc<dyn Fn(&FsEvent) -> bool + Send + Sync>;
+
+/// Callback run on the reader thread for every event that passes the filters.
+pub type EventHandler = std::sync::Arc<dyn Fn(&FsEvent) + Send + Sync>;
+
+/// Callback run on the reader thread for every runtime error, before it is
+/// queued on `FsUsageMonitor::errors()`.
+pub type ErrorHandler = std::sync::Arc<dyn Fn(&FsUsageError) + Send + Sync>;
+
 // Everything below this is macOS-only
 #[cfg(target_os = "macos")]
 mod macos_impl {
     use super::*;
-    use anyhow::{Context, Result};
-    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use crate::atomic_save::{run_detector, LogicalChange};
+    use crate::backpressure::{BackpressurePolicy, PolicySender, RingSender};
+    use crate::batch::run_batcher;
+    use crate::classifier::OperationClassifier;
+    use crate::debounce::{run_debouncer, FileChange};
+    use crate::error::Result;
+    use crate::fd_table::FdTable;
+    use crate::path_matcher::ExactPathMatcher;
+    use crate::preflight::{find_ktrace_session, PreflightReport};
+    use crate::process_info::{parent_pid, ProcessInfoCache};
+    use crate::process_tree::ProcessTree;
+    use crate::process_watch::{parent_table, read_processes, ProcessWatcher};
+    use crate::rate_limit::{RateLimitConfig, RateLimiter};
+    #[cfg(test)]
+    use crate::session::SessionEnd;
+    use crate::session::{FileSession, SessionTracker};
+    use crate::stream::EventStream;
+    use crate::subscription::{Subscribers, Subscription};
+    use crate::supervisor::{Backoff, RestartPolicy};
+    use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
     use glob::Pattern;
+    use regex::Regex;
+    use std::collections::HashSet;
+    use std::future::Future;
     use std::io::{BufRead, BufReader};
-    use std::process::{Child, Command, Stdio};
-    use std::sync::{Arc, Mutex};
-    use std::thread;
-    use tracing::{debug, error, info};
+    use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Stdio};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+    use tracing::{debug, error, info, warn};
 
     impl OperationType {
+        /// Checks `operation` against the built-in classification table. Use
+        /// `OperationClassifier` to customise the mapping.
         pub fn matches_operation(&self, operation: &str) -> bool {
-            match self {
-                OperationType::All => true,
-                OperationType::Read => matches!(
-                    operation,
-                    "read" | "pread" | "readv" | "preadv" | "RdData" | "RdMeta"
-                ),
-                OperationType::Write => {
-                    matches!(
-                        operation,
-                        "write"
-                            | "pwrite"
-                            | "writev"
-                            | "pwritev"
-                            | "WrData"
-                            | "WrMeta"
-                            | "ftruncate"
-                            | "rename"
-                            | "unlink"
-                            | "chmod_extended"
-                    ) || operation.starts_with("WrData[")
-                }
-                OperationType::Create => matches!(
-                    operation,
-                    "open" | "creat" | "mkdir" | "mkfifo" | "mknod" | "symlink" | "link"
-                ),
-                OperationType::Delete => matches!(operation, "unlink" | "rmdir" | "remove"),
-                OperationType::Move => matches!(operation, "rename" | "renameat"),
-                OperationType::Access => matches!(
-                    operation,
-                    "access"
-                        | "faccessat"
-                        | "stat"
-                        | "stat64"
-                        | "lstat"
-                        | "lstat64"
-                        | "fstat"
-                        | "fstat64"
-                ),
-                OperationType::Metadata => matches!(
-                    operation,
-                    "stat"
-                        | "stat64"
-                        | "lstat"
-                        | "lstat64"
-                        | "fstat"
-                        | "fstat64"
-                        | "getxattr"
-                        | "setxattr"
-                        | "listxattr"
-                        | "removexattr"
-                        | "getattrlist"
-                        | "setattrlist"
-                ),
-                OperationType::Chmod => matches!(operation, "chmod" | "chmod_extended"),
-            }
+            OperationClassifier::builtin().matches(self, operation)
         }
     }
 
@@ -119,10 +325,47 @@ mod macos_impl {
     pub struct FsUsageConfig {
         pub watch_paths: Vec<String>,
         pub watch_pids: Vec<u32>,
+        pub watch_processes: Vec<ProcessPattern>,
+        pub watch_pid_trees: Vec<u32>,
         pub exclude_pids: Vec<u32>,
         pub exclude_processes: Vec<String>,
         pub operation_types: Vec<OperationType>,
+        /// Maps fs_usage operation names to `OperationType` categories.
+        pub operation_classifier: OperationClassifier,
         pub exact_path_matching: bool,
+        /// Roots that relative fs_usage paths are resolved against when
+        /// `exact_path_matching` is enabled.
+        pub path_roots: Vec<String>,
+        /// With `exact_path_matching`, let relative paths match when they start
+        /// with a trailing portion of a watch path (e.g. `project/x` for
+        /// `/a/project`).
+        pub match_relative_tails: bool,
+        /// Per-path operation rules applied after the other filters. Empty
+        /// means no rule filtering.
+        pub path_rules: Vec<PathRule>,
+        pub rule_matching: RuleMatching,
+        pub rate_limit: RateLimitConfig,
+        /// Bound on queued events. `None` (the default) is unbounded. Read
+        /// when the monitor is created.
+        pub channel_capacity: Option<usize>,
+        /// What to do when a bounded channel is full.
+        pub backpressure: BackpressurePolicy,
+        /// Queue events on `events()`. Turn off when only streams or
+        /// subscriptions are used, so the unread queue neither grows nor,
+        /// under `BackpressurePolicy::Block`, stalls the reader.
+        pub events_channel: bool,
+        /// Poll the process table and send `ProcessStarted`/`ProcessExited`
+        /// on `monitor_events()`.
+        pub process_events: bool,
+        /// Attach `ProcessInfo` (ppid, executable, argv, uid, cwd) to every
+        /// sent event.
+        pub enrich_process_info: bool,
+        /// Tags every sent event with an `Actor`. `None` (the default)
+        /// disables attribution.
+        pub attributor: Option<Attributor>,
+        /// Restart fs_usage when it exits unexpectedly. `None` (the default)
+        /// stops the monitor instead.
+        pub restart_policy: Option<RestartPolicy>,
     }
 
     impl Default for FsUsageConfig {
@@ -130,6 +373,8 @@ mod macos_impl {
             Self {
                 watch_paths: vec![],
                 watch_pids: vec![],
+                watch_processes: vec![],
+                watch_pid_trees: vec![],
                 exclude_pids: vec![],
                 exclude_processes: vec![
                     "mds".to_string(),
@@ -137,133 +382,1106 @@ mod macos_impl {
                     "fseventsd".to_string(),
                 ],
                 operation_types: vec![OperationType::All],
+                operation_classifier: OperationClassifier::default(),
                 exact_path_matching: false,
+                path_roots: vec!["/".to_string(), "/System/Volumes/Data".to_string()],
+                match_relative_tails: false,
+                path_rules: vec![],
+                rule_matching: RuleMatching::FirstMatch,
+                rate_limit: RateLimitConfig::default(),
+                channel_capacity: None,
+                backpressure: BackpressurePolicy::default(),
+                events_channel: true,
+                process_events: false,
+                enrich_process_info: false,
+                attributor: None,
+                restart_policy: None,
             }
         }
     }
 
-    pub struct FsUsageMonitor {
+    #[derive(Debug, Clone)]
+    enum ProcessMatcher {
+        Exact(String),
+        Glob(Pattern),
+        Regex(Regex),
+    }
+
+    impl ProcessMatcher {
+        fn compile(pattern: &ProcessPattern) -> Result<Self> {
+            Ok(match pattern {
+                ProcessPattern::Exact(name) => ProcessMatcher::Exact(name.clone()),
+                ProcessPattern::Glob(glob) => ProcessMatcher::Glob(compile_glob(glob)?),
+                ProcessPattern::Regex(re) => {
+                    ProcessMatcher::Regex(Regex::new(re).map_err(|e| {
+                        FsUsageError::InvalidPattern {
+                            pattern: re.clone(),
+                            message: e.to_string(),
+                        }
+                    })?)
+                }
+            })
+        }
+
+        fn matches(&self, process_name: &str) -> bool {
+            match self {
+                ProcessMatcher::Exact(name) => name == process_name,
+                ProcessMatcher::Glob(pattern) => pattern.matches(process_name),
+                ProcessMatcher::Regex(re) => re.is_match(process_name),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct CompiledRule {
+        pattern: Pattern,
+        operations: Vec<OperationType>,
+        processes: Vec<ProcessMatcher>,
+    }
+
+    impl CompiledRule {
+        fn compile(rule: &PathRule) -> Result<Self> {
+            Ok(Self {
+                pattern: compile_glob(&rule.path)?,
+                operations: rule.operations.clone(),
+                processes: rule
+                    .processes
+                    .iter()
+                    .map(ProcessMatcher::compile)
+                    .collect::<Result<Vec<_>>>()?,
+            })
+        }
+
+        fn matches_target(&self, event: &FsEvent) -> bool {
+            event_paths(event).any(|path| self.pattern.matches(path))
+                && (self.processes.is_empty()
+                    || self
+                        .processes
+                        .iter()
+                        .any(|m| m.matches(&event.process_name)))
+        }
+
+        fn matches_operation(&self, event: &FsEvent, classifier: &OperationClassifier) -> bool {
+            self.operations
+                .iter()
+                .any(|op_type| classifier.matches_event(op_type, event))
+        }
+    }
+
+    fn compile_glob(pattern: &str) -> Result<Pattern> {
+        Pattern::new(pattern).map_err(|e| FsUsageError::InvalidPattern {
+            pattern: pattern.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// How often the process table is read, to refresh `watch_pid_tree`
+    /// membership and diff it for lifecycle events.
+    const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+    /// How long to wait for fs_usage to start producing output before
+    /// spawning a command under `spawn_command`.
+    const TRACING_STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+    /// fs_usage output lags the traced syscalls, so keep reading for a while
+    /// after the command exits before stopping the monitor.
+    const COMMAND_DRAIN_DELAY: Duration = Duration::from_millis(500);
+    /// How often `run_until` checks whether fs_usage exited on its own.
+    const RUN_UNTIL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+    /// How often events held back by `BackpressurePolicy::Coalesce` are
+    /// retried.
+    const COALESCE_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+    /// Lower bound on the rate-limit summary timer.
+    const MIN_SUMMARY_INTERVAL: Duration = Duration::from_millis(10);
+    /// Items kept on `monitor_events()` and `errors()` before the oldest are
+    /// discarded.
+    const STATUS_CHANNEL_CAPACITY: usize = 1024;
+
+    /// Result of `FsUsageMonitor::run_command`.
+    #[derive(Debug, Clone)]
+    pub struct CommandOutput {
+        pub status: ExitStatus,
+        pub events: Vec<FsEvent>,
+    }
+
+    /// Compiled filter state shared with the reader thread. Swapped as a whole
+    /// by `update_config` so the reader never sees a half-applied config.
+    struct FilterState {
         config: FsUsageConfig,
         patterns: Vec<Pattern>,
-        process: Option<Child>,
-        event_sender: Sender<FsEvent>,
-        event_receiver: Receiver<FsEvent>,
-        is_running: Arc<Mutex<bool>>,
+        process_matchers: Vec<ProcessMatcher>,
+        process_tree: Option<Arc<ProcessTree>>,
+        exact_path_matcher: Option<ExactPathMatcher>,
+        rules: Vec<CompiledRule>,
+        /// Shared so the reader can attribute events without holding the
+        /// lock during process lookups.
+        attributor: Option<Arc<Attributor>>,
+        /// Entries of `watch_pids` that have exited, so a reused PID is not
+        /// mistaken for the watched process.
+        exited_pids: HashSet<u32>,
     }
 
-    impl FsUsageMonitor {
-        pub fn new(config: FsUsageConfig) -> Result<Self> {
+    impl FilterState {
+        fn new(config: FsUsageConfig, process_tree: Option<Arc<ProcessTree>>) -> Result<Self> {
+            config.rate_limit.validate()?;
+
             let patterns = config
                 .watch_paths
                 .iter()
-                .map(|p| Pattern::new(p))
-                .collect::<Result<Vec<_>, _>>()
-                .context("Failed to compile glob patterns")?;
+                .map(|p| compile_glob(p))
+                .collect::<Result<Vec<_>>>()?;
+
+            let process_matchers = config
+                .watch_processes
+                .iter()
+                .map(ProcessMatcher::compile)
+                .collect::<Result<Vec<_>>>()?;
+
+            let process_tree = process_tree.or_else(|| {
+                if config.watch_pid_trees.is_empty() {
+                    None
+                } else {
+                    Some(Arc::new(ProcessTree::new(config.watch_pid_trees.clone())))
+                }
+            });
+
+            let exact_path_matcher = config.exact_path_matching.then(|| {
+                ExactPathMatcher::new(
+                    &config.watch_paths,
+                    &config.path_roots,
+                    config.match_relative_tails,
+                )
+            });
+
+            let rules = config
+                .path_rules
+                .iter()
+                .map(CompiledRule::compile)
+                .collect::<Result<Vec<_>>>()?;
 
-            let (event_sender, event_receiver) = unbounded();
+            let attributor = config.attributor.clone().map(Arc::new);
 
             Ok(Self {
                 config,
                 patterns,
-                process: None,
+                process_matchers,
+                process_tree,
+                exact_path_matcher,
+                rules,
+                attributor,
+                exited_pids: HashSet::new(),
+            })
+        }
+
+        /// Marks watched PIDs whose process is already gone as exited. The
+        /// process poller only reports exits it observes, so PIDs that were
+        /// dead when they were configured are caught here.
+        fn mark_dead_watch_pids(&mut self, is_alive: impl Fn(u32) -> bool) {
+            for pid in &self.config.watch_pids {
+                if !self.exited_pids.contains(pid) && !is_alive(*pid) {
+                    debug!("Watched PID {} is not running", pid);
+                    self.exited_pids.insert(*pid);
+                }
+            }
+        }
+
+        /// Arguments passed to fs_usage. Changing any of these requires a restart.
+        fn fs_usage_args(&self) -> Vec<String> {
+            let config = &self.config;
+            let mut args = vec![
+                "-w".to_string(), // Wide format for detailed output
+                "-f".to_string(),
+                "pathname,filesys".to_string(), // Both pathname and filesys events for better coverage
+            ];
+
+            // Only add -p flags if we have specific PIDs to watch. Process trees
+            // grow at runtime, so they are filtered in should_send_event instead.
+            if !config.watch_pids.is_empty() && self.process_tree.is_none() {
+                for pid in &config.watch_pids {
+                    args.push("-p".to_string());
+                    args.push(pid.to_string());
+                }
+            }
+
+            // fs_usage accepts command names as trailing arguments, which narrows
+            // tracing at the source. Only exact names can be passed this way, and
+            // the positional list would conflict with -e, so exclusions are left
+            // out when an exact allowlist is in effect.
+            let exact_processes: Option<Vec<&str>> = config
+                .watch_processes
+                .iter()
+                .map(|p| match p {
+                    ProcessPattern::Exact(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect();
+
+            match exact_processes {
+                Some(names)
+                    if !names.is_empty()
+                        && config.watch_pids.is_empty()
+                        && self.process_tree.is_none() =>
+                {
+                    args.extend(names.into_iter().map(String::from));
+                }
+                _ => {
+                    for process in &config.exclude_processes {
+                        args.push("-e".to_string());
+                        args.push(process.clone());
+                    }
+                }
+            }
+
+            args
+        }
+    }
+
+    pub struct FsUsageMonitor {
+        filter_state: Arc<RwLock<FilterState>>,
+        filters: Vec<EventFilter>,
+        event_handlers: Vec<EventHandler>,
+        error_handlers: Vec<ErrorHandler>,
+        process: Arc<Mutex<Option<Child>>>,
+        reader: Option<JoinHandle<()>>,
+        shutdown: Option<Sender<()>>,
+        event_sender: Sender<FsEvent>,
+        event_receiver: Receiver<FsEvent>,
+        monitor_event_sender: MonitorEventSender,
+        monitor_event_receiver: Receiver<MonitorEvent>,
+        error_sender: RingSender<FsUsageError>,
+        error_receiver: Receiver<FsUsageError>,
+        subscribers: Subscribers,
+        /// Threads behind `batches()`, `debounce()` and `logical_changes()`,
+        /// joined by `stop()`.
+        helpers: Mutex<Vec<JoinHandle<()>>>,
+        session_senders: Arc<Mutex<Vec<Sender<FileSession>>>>,
+        dropped_events: Arc<AtomicU64>,
+        restart_count: Arc<AtomicU64>,
+        is_running: Arc<Mutex<bool>>,
+        is_tracing: Arc<Mutex<bool>>,
+        /// Checks the environment and starts fs_usage, for `start()` and each
+        /// restart; replaced in tests.
+        launch: Launcher,
+    }
+
+    type Launcher = fn(&[String]) -> Result<(Child, ChildStdout, StderrCapture)>;
+
+    impl FsUsageMonitor {
+        pub fn new(config: FsUsageConfig) -> Result<Self> {
+            let (event_sender, event_receiver) = match config.channel_capacity {
+                Some(capacity) => bounded(capacity),
+                None => unbounded(),
+            };
+
+            let (monitor_event_sender, monitor_event_receiver) = bounded(STATUS_CHANNEL_CAPACITY);
+            let (error_sender, error_receiver) = bounded(STATUS_CHANNEL_CAPACITY);
+            let dropped_events = Arc::new(AtomicU64::new(0));
+            let subscribers = Subscribers::new(
+                config.channel_capacity,
+                config.backpressure,
+                dropped_events.clone(),
+            );
+            let filter_state = FilterState::new(config, None)?;
+
+            Ok(Self {
+                filter_state: Arc::new(RwLock::new(filter_state)),
+                filters: Vec::new(),
+                event_handlers: Vec::new(),
+                error_handlers: Vec::new(),
+                process: Arc::new(Mutex::new(None)),
+                reader: None,
+                shutdown: None,
                 event_sender,
                 event_receiver,
+                monitor_event_sender: MonitorEventSender {
+                    sender: RingSender::new(monitor_event_sender, monitor_event_receiver.clone()),
+                    subscribers: subscribers.clone(),
+                },
+                monitor_event_receiver,
+                error_sender: RingSender::new(error_sender, error_receiver.clone()),
+                error_receiver,
+                subscribers,
+                helpers: Mutex::new(Vec::new()),
+                session_senders: Arc::new(Mutex::new(Vec::new())),
+                dropped_events,
+                restart_count: Arc::new(AtomicU64::new(0)),
                 is_running: Arc::new(Mutex::new(false)),
+                is_tracing: Arc::new(Mutex::new(false)),
+                launch: launch_fs_usage,
             })
         }
 
+        pub(crate) fn with_filters(mut self, filters: Vec<EventFilter>) -> Self {
+            self.filters = filters;
+            self
+        }
+
+        pub(crate) fn with_handlers(
+            mut self,
+            event_handlers: Vec<EventHandler>,
+            error_handlers: Vec<ErrorHandler>,
+        ) -> Self {
+            self.event_handlers = event_handlers;
+            self.error_handlers = error_handlers;
+            self
+        }
+
+        /// Checks that fs_usage can run here: the binary, root privileges and
+        /// a free ktrace session. `start()` runs the same checks and fails
+        /// with the first problem found.
+        pub fn preflight() -> PreflightReport {
+            PreflightReport::run()
+        }
+
         pub fn start(&mut self) -> Result<()> {
             if *self.is_running.lock().unwrap() {
-                return Err(anyhow::anyhow!("Monitor is already running"));
+                return Err(FsUsageError::AlreadyRunning);
             }
+            let args = {
+                let mut state = self.filter_state.write().unwrap();
+                state.mark_dead_watch_pids(|pid| parent_pid(pid).is_some());
+                state.fs_usage_args()
+            };
 
-            let mut cmd = Command::new("fs_usage");
-            cmd.arg("-w") // Wide format for detailed output
-                .arg("-f")
-                .arg("pathname,filesys") // Both pathname and filesys events for better coverage
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null());
+            let (child, stdout, stderr) = (self.launch)(&args)?;
 
-            // Only add -p flags if we have specific PIDs to watch
-            if !self.config.watch_pids.is_empty() {
-                for pid in &self.config.watch_pids {
-                    cmd.arg("-p").arg(pid.to_string());
+            *self.is_running.lock().unwrap() = true;
+            *self.is_tracing.lock().unwrap() = false;
+            *self.process.lock().unwrap() = Some(child);
+
+            let (shutdown, shutdown_signal) = bounded::<()>(0);
+            self.shutdown = Some(shutdown);
+            let restart_signal = shutdown_signal.clone();
+
+            let session_tracker = Arc::new(Mutex::new(SessionTracker::default()));
+            let mut poller = ProcessPoller {
+                watcher: None,
+                filter_state: self.filter_state.clone(),
+                monitor_sender: self.monitor_event_sender.clone(),
+                session_tracker: session_tracker.clone(),
+                session_senders: self.session_senders.clone(),
+            };
+            // The first snapshot is taken before any event is read, so the
+            // process tree starts out populated
+            poller.poll();
+            let poller_shutdown = shutdown_signal.clone();
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) =
+                    poller_shutdown.recv_timeout(PROCESS_POLL_INTERVAL)
+                {
+                    poller.poll();
                 }
+            });
+
+            let rate_limiter = Arc::new(Mutex::new(RateLimiter::new(Instant::now())));
+            {
+                let rate_limiter = rate_limiter.clone();
+                let filter_state = self.filter_state.clone();
+                let monitor_sender = self.monitor_event_sender.clone();
+                let shutdown_signal = shutdown_signal.clone();
+                thread::spawn(move || loop {
+                    // Summaries come from a timer so drops are reported even
+                    // when no further events arrive
+                    let interval = filter_state
+                        .read()
+                        .unwrap()
+                        .config
+                        .rate_limit
+                        .summary_interval;
+                    if !matches!(
+                        shutdown_signal.recv_timeout(interval.max(MIN_SUMMARY_INTERVAL)),
+                        Err(RecvTimeoutError::Timeout)
+                    ) {
+                        break;
+                    }
+                    let config = filter_state.read().unwrap().config.rate_limit.clone();
+                    let summaries = rate_limiter
+                        .lock()
+                        .unwrap()
+                        .summaries(&config, Instant::now());
+                    for summary in summaries {
+                        monitor_sender.send(summary);
+                    }
+                });
             }
 
-            for process in &self.config.exclude_processes {
-                cmd.arg("-e").arg(process);
+            let policy = self.filter_state.read().unwrap().config.backpressure;
+            let sender = Arc::new(Mutex::new(PolicySender::new(
+                self.event_sender.clone(),
+                self.event_receiver.clone(),
+                policy,
+                self.dropped_events.clone(),
+            )));
+            if policy == BackpressurePolicy::Coalesce {
+                let sender = sender.clone();
+                let subscribers = self.subscribers.clone();
+                let shutdown_signal = shutdown_signal.clone();
+                thread::spawn(move || {
+                    // Deliver held-back events once the consumer makes room,
+                    // even if no further events arrive
+                    while let Err(RecvTimeoutError::Timeout) =
+                        shutdown_signal.recv_timeout(COALESCE_FLUSH_INTERVAL)
+                    {
+                        subscribers.flush();
+                        if sender.lock().unwrap().flush().is_err() {
+                            break;
+                        }
+                    }
+                });
             }
 
-            info!("Starting fs_usage monitor with args: {:?}", cmd);
-            let mut child = cmd.spawn().context("Failed to spawn fs_usage process")?;
+            let monitor_sender = self.monitor_event_sender.clone();
+            let subscribers = self.subscribers.clone();
+            let session_senders = self.session_senders.clone();
+            let filter_state = self.filter_state.clone();
+            let filters = self.filters.clone();
+            let event_handlers = self.event_handlers.clone();
+            let queue_events = self.event_handlers.is_empty()
+                && self.filter_state.read().unwrap().config.events_channel;
+            let errors = ErrorReporter {
+                handlers: self.error_handlers.clone(),
+                sender: self.error_sender.clone(),
+            };
+            let is_running = self.is_running.clone();
+            let is_tracing = self.is_tracing.clone();
+            let process = self.process.clone();
+            let launch = self.launch;
+            let restart_count = self.restart_count.clone();
+            let dropped_events = self.dropped_events.clone();
+            let mut backoff = self
+                .filter_state
+                .read()
+                .unwrap()
+                .config
+                .restart_policy
+                .clone()
+                .map(Backoff::new);
+
+            self.reader = Some(thread::spawn(move || {
+                let (mut stdout, mut stderr) = (stdout, stderr);
+                let mut fd_table = FdTable::default();
+                let mut process_info = ProcessInfoCache::new();
+                'supervise: loop {
+                    let started = Instant::now();
+                    let mut seen_output = false;
+                    for line in BufReader::new(stdout).lines() {
+                        if !*is_running.lock().unwrap() {
+                            break;
+                        }
+
+                        match line {
+                            Ok(line) => {
+                                if !seen_output {
+                                    seen_output = true;
+                                    *is_tracing.lock().unwrap() = true;
+                                }
+                                debug!("Raw fs_usage line: {}", line);
+                                let Some(mut event) = parse_fs_usage_line(&line) else {
+                                    debug!("Failed to parse line: {}", line);
+                                    if looks_like_trace_record(&line) {
+                                        errors.report(FsUsageError::ParseError { line });
+                                    }
+                                    continue;
+                                };
+                                fd_table.resolve(&mut event);
+                                debug!("Parsed event: {:?}", event);
+
+                                // Ancestry lookups for PIDs the process tree has
+                                // not seen yet also stay outside the filter lock
+                                let process_tree =
+                                    filter_state.read().unwrap().process_tree.clone();
+                                if let Some(tree) = process_tree {
+                                    tree.classify(event.pid);
+                                }
+
+                                let tracking_sessions = !session_senders.lock().unwrap().is_empty();
+                                let (send, sessions, enrich, attributor) = {
+                                    let state = filter_state.read().unwrap();
+                                    let rate_limit = &state.config.rate_limit;
+                                    // Descriptor-only events (close, unresolved I/O) have no
+                                    // path; they only feed session tracking
+                                    let matches = !event.path.is_empty()
+                                        && should_send_event(&event, &state)
+                                        && passes_filters(&event, &filters);
+                                    let sessions = if tracking_sessions {
+                                        session_tracker.lock().unwrap().observe(
+                                            &event,
+                                            matches,
+                                            &state.config.operation_classifier,
+                                        )
+                                    } else {
+                                        Vec::new()
+                                    };
+                                    let send = if !matches {
+                                        debug!("Event filtered out: {:?}", event);
+                                        false
+                                    } else if rate_limit.is_enabled()
+                                        && !rate_limiter.lock().unwrap().allow(
+                                            &event,
+                                            rate_limit,
+                                            Instant::now(),
+                                        )
+                                    {
+                                        debug!("Event rate limited: {:?}", event);
+                                        dropped_events.fetch_add(1, Ordering::Relaxed);
+                                        false
+                                    } else {
+                                        true
+                                    };
+                                    (
+                                        send,
+                                        sessions,
+                                        state.config.enrich_process_info,
+                                        state.attributor.clone(),
+                                    )
+                                };
+
+                                // Process lookups happen outside the filter lock
+                                // so update_config is never blocked by them
+                                if send {
+                                    if enrich {
+                                        event.process_info = process_info.get(event.pid).cloned();
+                                    }
+                                    if let Some(attributor) = attributor {
+                                        event.actor =
+                                            Some(attributor.attribute(&event, &mut process_info));
+                                    }
+                                }
+
+                                if event.operation == "exit" {
+                                    process_info.invalidate(event.pid);
+                                }
+
+                                send_sessions(&session_senders, &sessions);
+
+                                if send {
+                                    debug!("Sending event for path: {}", event.path);
+                                    let keep_waiting = || *is_running.lock().unwrap();
+                                    subscribers.publish(&event, &keep_waiting);
+                                    for handler in &event_handlers {
+                                        handler(&event);
+                                    }
+                                    // Handlers replace the channel so their users
+                                    // don't accumulate unread events; otherwise it
+                                    // is skipped only when explicitly turned off
+                                    if !queue_events {
+                                        continue;
+                                    }
+                                    let sent = sender.lock().unwrap().send(event, keep_waiting);
+                                    if sent.is_err() {
+                                        errors.report(FsUsageError::ChannelClosed);
+                                        break 'supervise;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                errors
+                                    .report(FsUsageError::io("Failed to read fs_usage output", e));
+                                break;
+                            }
+                        }
+                    }
+
+                    // Still marked running means fs_usage went away on its own
+                    if !*is_running.lock().unwrap() {
+                        break;
+                    }
+                    // Reaped outside the lock so a concurrent stop() is never
+                    // blocked on it
+                    let exited = process.lock().unwrap().take();
+                    let status = exited.and_then(|mut child| {
+                        // fs_usage may have closed stdout without exiting
+                        let _ = child.kill();
+                        child.wait().ok()
+                    });
+                    let mut err = FsUsageError::child_exited(status, stderr.finish());
+                    if let FsUsageError::KtraceBusy { holder } = &mut err {
+                        *holder = find_ktrace_session();
+                    }
+                    let mut last_error = err.to_string();
+                    errors.report(err);
+                    let Some(backoff) = backoff.as_mut() else {
+                        break;
+                    };
+
+                    let mut uptime = started.elapsed();
+                    (stdout, stderr) = loop {
+                        let Some(delay) = backoff.next_delay(uptime) else {
+                            error!("fs_usage restart limit reached, stopping monitor");
+                            monitor_sender.send(MonitorEvent::MonitorFailed {
+                                attempts: backoff.attempts(),
+                                error: last_error,
+                            });
+                            break 'supervise;
+                        };
+                        info!("Restarting fs_usage in {:?}", delay);
+                        // stop() drops the shutdown sender, ending the wait early
+                        if !matches!(
+                            restart_signal.recv_timeout(delay),
+                            Err(RecvTimeoutError::Timeout)
+                        ) {
+                            break 'supervise;
+                        }
+
+                        let args = filter_state.read().unwrap().fs_usage_args();
+                        // Rechecks the environment so a restart blocked by
+                        // another ktrace session is reported as such
+                        match launch(&args) {
+                            Ok((mut child, stdout, stderr)) => {
+                                let mut process = process.lock().unwrap();
+                                // stop() clears is_running before taking the
+                                // child, so checking under the lock never leaks
+                                // a new fs_usage
+                                if !*is_running.lock().unwrap() {
+                                    drop(process);
+                                    let _ = child.kill();
+                                    let _ = child.wait();
+                                    break 'supervise;
+                                }
+                                *process = Some(child);
+                                break (stdout, stderr);
+                            }
+                            Err(e) => {
+                                last_error = e.to_string();
+                                errors.report(e);
+                                uptime = Duration::ZERO;
+                            }
+                        }
+                    };
+
+                    restart_count.fetch_add(1, Ordering::Relaxed);
+                    *is_tracing.lock().unwrap() = false;
+                    monitor_sender.send(MonitorEvent::MonitorRestarted {
+                        attempt: backoff.attempts(),
+                    });
+                }
+                *is_running.lock().unwrap() = false;
+            }));
+
+            Ok(())
+        }
 
-            let stdout = child
-                .stdout
-                .take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to capture stdout"))?;
+        /// Stops fs_usage and ends every stream, subscription and derived
+        /// feed such as `batches()`, whose threads are joined after flushing.
+        pub fn stop(&mut self) -> Result<()> {
+            let stopped = self.stop_process();
+            self.subscribers.close();
+            for helper in self.helpers.lock().unwrap().drain(..) {
+                let _ = helper.join();
+            }
+            stopped
+        }
+
+        /// Stops fs_usage and the reader, keeping consumers attached for a
+        /// restart.
+        fn stop_process(&mut self) -> Result<()> {
+            *self.is_running.lock().unwrap() = false;
+            self.shutdown = None;
+
+            let process = self.process.lock().unwrap().take();
+            if let Some(mut process) = process {
+                info!("Stopping fs_usage monitor");
+                process
+                    .kill()
+                    .map_err(|e| FsUsageError::io("Failed to kill fs_usage process", e))?;
+                process
+                    .wait()
+                    .map_err(|e| FsUsageError::io("Failed to wait for process", e))?;
+            }
+
+            // The reader exits once fs_usage's stdout closes; joining it keeps a
+            // stale reader from clearing is_running after a restart.
+            if let Some(reader) = self.reader.take() {
+                let _ = reader.join();
+            }
+
+            Ok(())
+        }
+
+        /// Number of times fs_usage was restarted under the `restart_policy`
+        /// since the monitor was created.
+        pub fn restart_count(&self) -> u64 {
+            self.restart_count.load(Ordering::Relaxed)
+        }
+
+        pub fn is_running(&self) -> bool {
+            *self.is_running.lock().unwrap()
+        }
+
+        /// Starts the monitor if needed and blocks until `signal` completes or
+        /// fs_usage exits, then stops fs_usage.
+        ///
+        /// This builds and drives its own runtime, so it returns an
+        /// `InvalidState` error when called from within a tokio runtime; use
+        /// `stream()` there instead.
+        pub fn run_until<F: Future>(&mut self, signal: F) -> Result<()> {
+            if tokio::runtime::Handle::try_current().is_ok() {
+                return Err(FsUsageError::InvalidState(
+                    "run_until() cannot be called from within a tokio runtime".to_string(),
+                ));
+            }
+
+            if !self.is_running() {
+                self.start()?;
+            }
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| FsUsageError::io("Failed to create runtime", e))?;
+            let is_running = self.is_running.clone();
+            runtime.block_on(async {
+                let exited = async {
+                    let mut interval = tokio::time::interval(RUN_UNTIL_POLL_INTERVAL);
+                    while *is_running.lock().unwrap() {
+                        interval.tick().await;
+                    }
+                };
+                tokio::select! {
+                    _ = signal => {}
+                    _ = exited => info!("fs_usage exited"),
+                }
+            });
+
+            self.stop()
+        }
+
+        /// Like `run_until`, stopping at Ctrl-C. This installs tokio's SIGINT
+        /// handler, which replaces the default handler for the rest of the
+        /// process's life: later Ctrl-Cs no longer terminate the program.
+        pub fn run_until_ctrl_c(&mut self) -> Result<()> {
+            self.run_until(async {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    info!("Received Ctrl-C");
+                }
+            })
+        }
+
+        /// Returns a copy of the configuration currently used for filtering.
+        pub fn config(&self) -> FsUsageConfig {
+            self.filter_state.read().unwrap().config.clone()
+        }
+
+        /// Replaces the monitor's configuration.
+        ///
+        /// Path, PID and operation filters are swapped atomically on the
+        /// running reader thread without interrupting fs_usage. fs_usage is only
+        /// restarted when the arguments it is launched with (`-p`, `-e` or the
+        /// watched command names) change. On error the previous configuration
+        /// stays in effect, and if the restart failed the monitor is started
+        /// again with it.
+        ///
+        /// `channel_capacity` and `backpressure` are fixed when the monitor is
+        /// created, and `restart_policy` and `events_channel` are read by
+        /// `start()`, so changing the former, or the latter while running,
+        /// is rejected with `InvalidState`.
+        pub fn update_config(&mut self, config: FsUsageConfig) -> Result<()> {
+            {
+                let current = &self.filter_state.read().unwrap().config;
+                if config.channel_capacity != current.channel_capacity
+                    || config.backpressure != current.backpressure
+                {
+                    return Err(FsUsageError::InvalidState(
+                        "channel_capacity and backpressure cannot be changed after the monitor is created"
+                            .to_string(),
+                    ));
+                }
+                if self.is_running()
+                    && (config.restart_policy != current.restart_policy
+                        || config.events_channel != current.events_channel)
+                {
+                    return Err(FsUsageError::InvalidState(
+                        "restart_policy and events_channel cannot be changed while the monitor is running"
+                            .to_string(),
+                    ));
+                }
+            }
+
+            let (old_args, process_tree, exited_pids, old_watch_pids) = {
+                let state = self.filter_state.read().unwrap();
+                // Keep the live tree (and any roots added by spawn_command) unless
+                // the configured roots changed.
+                let tree = if state.config.watch_pid_trees == config.watch_pid_trees {
+                    state.process_tree.clone()
+                } else {
+                    None
+                };
+                (
+                    state.fs_usage_args(),
+                    tree,
+                    state.exited_pids.clone(),
+                    state.config.watch_pids.clone(),
+                )
+            };
+
+            let mut new_state = FilterState::new(config, process_tree)?;
+            // Only PIDs watched throughout keep their exit mark; a PID added
+            // again may now belong to a new process, so it is checked afresh
+            new_state.exited_pids = exited_pids
+                .into_iter()
+                .filter(|pid| {
+                    old_watch_pids.contains(pid) && new_state.config.watch_pids.contains(pid)
+                })
+                .collect();
+            new_state.mark_dead_watch_pids(|pid| parent_pid(pid).is_some());
+            let restart = self.is_running() && new_state.fs_usage_args() != old_args;
+
+            if restart {
+                info!("fs_usage arguments changed, restarting monitor");
+                self.stop_process()?;
+            }
+
+            let old_state = std::mem::replace(&mut *self.filter_state.write().unwrap(), new_state);
+
+            if restart {
+                if let Err(e) = self.start() {
+                    *self.filter_state.write().unwrap() = old_state;
+                    if let Err(restore) = self.start() {
+                        warn!(
+                            "Failed to restart with the previous configuration: {}",
+                            restore
+                        );
+                    }
+                    return Err(e);
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Adds a glob pattern to `watch_paths` without restarting fs_usage.
+        pub fn add_watch_path(&mut self, path: impl Into<String>) -> Result<()> {
+            let mut config = self.config();
+            config.watch_paths.push(path.into());
+            self.update_config(config)
+        }
+
+        /// Removes a pattern previously added to `watch_paths`. Returns whether
+        /// the pattern was present.
+        pub fn remove_watch_path(&mut self, path: &str) -> Result<bool> {
+            let mut config = self.config();
+            let before = config.watch_paths.len();
+            config.watch_paths.retain(|p| p != path);
+            if config.watch_paths.len() == before {
+                return Ok(false);
+            }
+            self.update_config(config)?;
+            Ok(true)
+        }
+
+        /// Starts the monitor, spawns `command` and scopes events to the
+        /// command's process tree. Events are delivered on `events()` as usual;
+        /// the caller is responsible for waiting on the child and calling
+        /// `stop()`.
+        ///
+        /// Any `watch_pids` or `watch_pid_tree` roots already configured stay
+        /// in effect alongside the command's tree, and the command's PID stays
+        /// tracked if the monitor is restarted.
+        pub fn spawn_command(&mut self, command: &mut Command) -> Result<Child> {
+            if self.is_running() {
+                return Err(FsUsageError::InvalidState(
+                    "spawn_command must be called on a stopped monitor".to_string(),
+                ));
+            }
+
+            let tree = self.command_tree();
+            self.start()?;
+            self.wait_for_tracing(TRACING_STARTUP_TIMEOUT);
+
+            let child = match command.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = self.stop_process();
+                    return Err(FsUsageError::io("Failed to spawn command", e));
+                }
+            };
+            tree.add_root(child.id());
+            info!("Monitoring command with PID {}", child.id());
+
+            Ok(child)
+        }
+
+        /// The process tree that `spawn_command` adds its command to.
+        fn command_tree(&self) -> Arc<ProcessTree> {
+            self.filter_state
+                .write()
+                .unwrap()
+                .process_tree
+                .get_or_insert_with(|| Arc::new(ProcessTree::new(Vec::new())))
+                .clone()
+        }
+
+        /// Runs `command` to completion while monitoring its process tree and
+        /// returns its exit status together with every event it produced. The
+        /// monitor is stopped once the command exits.
+        ///
+        /// Events are collected on a dedicated unbounded subscription, so
+        /// they are returned whatever the channel capacity, backpressure
+        /// policy or other consumers, and `events()` is left untouched. With
+        /// a bounded channel under `BackpressurePolicy::Block`, drain
+        /// `events()` meanwhile or turn it off with `events_channel(false)`,
+        /// or the reader stalls once it is full.
+        pub fn run_command(&mut self, mut command: Command) -> Result<CommandOutput> {
+            let tree = self.command_tree();
+            let subscription = self
+                .subscribers
+                .subscribe_unbounded(Arc::new(move |event: &FsEvent| tree.contains(event.pid)));
+            let mut child = self.spawn_command(&mut command)?;
+            let status = child
+                .wait()
+                .map_err(|e| FsUsageError::io("Failed to wait for command", e));
+
+            thread::sleep(COMMAND_DRAIN_DELAY);
+            self.stop()?;
+
+            let events = subscription.events().try_iter().collect();
+            Ok(CommandOutput {
+                status: status?,
+                events,
+            })
+        }
+
+        fn wait_for_tracing(&self, timeout: Duration) {
+            let started = std::time::Instant::now();
+            while self.is_running()
+                && !*self.is_tracing.lock().unwrap()
+                && started.elapsed() < timeout
+            {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        /// PIDs currently matched by `watch_pid_tree`, roots included.
+        pub fn tracked_pids(&self) -> Vec<u32> {
+            self.filter_state
+                .read()
+                .unwrap()
+                .process_tree
+                .as_ref()
+                .map(|tree| tree.pids())
+                .unwrap_or_default()
+        }
+
+        /// Runtime errors such as fs_usage exiting, read failures and
+        /// unparseable trace records, also passed to `on_error` handlers. Only
+        /// the latest errors are kept if the channel is not drained.
+        pub fn errors(&self) -> &Receiver<FsUsageError> {
+            &self.error_receiver
+        }
+
+        /// Status events such as rate-limit suppression summaries. Only the
+        /// latest events are kept if the channel is not drained.
+        pub fn monitor_events(&self) -> &Receiver<MonitorEvent> {
+            &self.monitor_event_receiver
+        }
 
-            *self.is_running.lock().unwrap() = true;
-            self.process = Some(child);
+        /// Returns a subscription carrying every file event and monitor
+        /// event, such as process lifecycle events, in the order they were
+        /// produced. Like other subscriptions it is bounded by
+        /// `channel_capacity` under the backpressure policy, and it ends when
+        /// the monitor is stopped or dropped.
+        pub fn activity(&self) -> Subscription<Activity> {
+            self.subscribers.subscribe_activity()
+        }
 
-            let sender = self.event_sender.clone();
-            let patterns = self.patterns.clone();
-            let config = self.config.clone();
-            let is_running = self.is_running.clone();
+        /// Returns an async `Stream` of events. Each call creates an
+        /// independent stream that receives every event from then on, with
+        /// its own channel bounded by `channel_capacity` and governed by the
+        /// backpressure policy. It ends when the monitor is stopped or
+        /// dropped.
+        pub fn stream(&self) -> EventStream {
+            self.subscribers.subscribe_stream()
+        }
 
-            thread::spawn(move || {
-                let reader = BufReader::new(stdout);
-                for line in reader.lines() {
-                    if !*is_running.lock().unwrap() {
-                        break;
-                    }
+        /// Adds an independent consumer that receives every event matching
+        /// `filter`, sharing this monitor's fs_usage process. `filter` runs on
+        /// the reader thread after the monitor's own filters. Dropping the
+        /// `Subscription` unsubscribes; stopping the monitor ends it. Each
+        /// subscription has its own channel, bounded by `channel_capacity`
+        /// under the backpressure policy, and events keep being queued on
+        /// `events()` unless `events_channel` is turned off.
+        pub fn subscribe<F>(&self, filter: F) -> Subscription
+        where
+            F: Fn(&FsEvent) -> bool + Send + Sync + 'static,
+        {
+            self.subscribers.subscribe(Arc::new(filter))
+        }
 
-                    match line {
-                        Ok(line) => {
-                            debug!("Raw fs_usage line: {}", line);
-                            if let Some(event) = parse_fs_usage_line(&line) {
-                                debug!("Parsed event: {:?}", event);
-                                if should_send_event(&event, &patterns, &config) {
-                                    debug!("Sending event for path: {}", event.path);
-                                    if let Err(e) = sender.send(event) {
-                                        error!("Failed to send event: {}", e);
-                                        break;
-                                    }
-                                } else {
-                                    debug!("Event filtered out: {:?}", event);
-                                }
-                            } else {
-                                debug!("Failed to parse line: {}", line);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Error reading line: {}", e);
-                            break;
-                        }
-                    }
-                }
-                *is_running.lock().unwrap() = false;
+        /// Delivers events in order as batches of up to `max_len`, sending a
+        /// partial batch `max_wait` after its first event. Batching runs on
+        /// its own thread, which flushes and exits when the monitor is stopped
+        /// or dropped, or at the next batch after the receiver is dropped.
+        pub fn batches(&self, max_len: usize, max_wait: Duration) -> Receiver<Vec<FsEvent>> {
+            let subscription = self.subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+            let (sender, receiver) = unbounded();
+            self.spawn_helper(move || {
+                run_batcher(subscription.events(), &sender, max_len, max_wait)
             });
+            receiver
+        }
 
-            Ok(())
+        /// Runs a derived feed on its own thread. Its subscription is closed
+        /// by `stop()`, which ends the thread.
+        fn spawn_helper(&self, helper: impl FnOnce() + Send + 'static) {
+            let mut helpers = self.helpers.lock().unwrap();
+            helpers.retain(|helper| !helper.is_finished());
+            helpers.push(thread::spawn(helper));
         }
 
-        pub fn stop(&mut self) -> Result<()> {
-            *self.is_running.lock().unwrap() = false;
+        /// Coalesces bursts of events on the same path by the same process
+        /// into one `FileChange`, sent once that pair has seen no events for
+        /// `quiet`. A file touched continuously is reported only after the
+        /// activity stops. Pending changes are sent when the monitor is
+        /// stopped or dropped, which ends the debouncing thread.
+        pub fn debounce(&self, quiet: Duration) -> Receiver<FileChange> {
+            let subscription = self.subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+            let (sender, receiver) = unbounded();
+            self.spawn_helper(move || run_debouncer(subscription.events(), &sender, quiet));
+            receiver
+        }
 
-            if let Some(mut process) = self.process.take() {
-                info!("Stopping fs_usage monitor");
-                process.kill().context("Failed to kill fs_usage process")?;
-                process.wait().context("Failed to wait for process")?;
-            }
+        /// Groups each watched file descriptor's activity between open and
+        /// close into a `FileSession`, sent when the descriptor is closed or
+        /// the process exits. Exits are detected by polling the process table
+        /// every 500ms, so a session can end shortly after its process.
+        /// Sessions start at opens that pass the monitor's filters; files
+        /// opened before `start()` are not tracked.
+        pub fn sessions(&self) -> Receiver<FileSession> {
+            let (sender, receiver) = unbounded();
+            self.session_senders.lock().unwrap().push(sender);
+            receiver
+        }
 
-            Ok(())
+        /// Reports atomic saves (a temp file renamed over the target, or the
+        /// target renamed to a backup and rewritten) as a single
+        /// `LogicalChange::Modified` for the real path. Raw events, including
+        /// those for the temp file, still reach streams and subscriptions.
+        /// Detection runs on its own thread, which ends when the monitor is
+        /// stopped or dropped.
+        pub fn logical_changes(&self) -> Receiver<LogicalChange> {
+            let config = self.config();
+            let subscription = self.subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+            let (sender, receiver) = unbounded();
+            self.spawn_helper(move || {
+                run_detector(
+                    subscription.events(),
+                    &sender,
+                    config.operation_classifier,
+                    &config.path_roots,
+                )
+            });
+            receiver
         }
 
-        pub fn is_running(&self) -> bool {
-            *self.is_running.lock().unwrap()
+        /// Number of events discarded by rate limiting or sampling, or
+        /// discarded or coalesced because the event channel, or a stream's or
+        /// subscription's channel, was full. Unlike the `Suppressed`
+        /// summaries on `monitor_events()`, which only keep the latest
+        /// events, this count is never lost.
+        pub fn dropped_events(&self) -> u64 {
+            self.dropped_events.load(Ordering::Relaxed)
         }
 
+        /// The main event channel. It is not filled when `on_event` handlers
+        /// are set or `events_channel` is turned off.
         pub fn events(&self) -> &Receiver<FsEvent> {
             &self.event_receiver
         }
@@ -275,7 +1493,7 @@ mod macos_impl {
         pub fn recv(&self) -> Result<FsEvent> {
             self.event_receiver
                 .recv()
-                .context("Failed to receive event")
+                .map_err(|_| FsUsageError::ChannelClosed)
         }
     }
 
@@ -285,6 +1503,233 @@ mod macos_impl {
         }
     }
 
+    /// Polls the process table for lifecycle events, which feed
+    /// `monitor_events()`, the exit of `watch_pids` entries and the end of
+    /// sessions of exited processes.
+    struct ProcessPoller {
+        /// `None` while nothing needs process events; the first snapshot
+        /// after a pause is a fresh baseline.
+        watcher: Option<ProcessWatcher>,
+        filter_state: Arc<RwLock<FilterState>>,
+        monitor_sender: MonitorEventSender,
+        session_tracker: Arc<Mutex<SessionTracker>>,
+        session_senders: Arc<Mutex<Vec<Sender<FileSession>>>>,
+    }
+
+    impl ProcessPoller {
+        fn poll(&mut self) {
+            let (needed, tree) = {
+                let state = self.filter_state.read().unwrap();
+                let config = &state.config;
+                (
+                    config.process_events || !config.watch_pids.is_empty(),
+                    state.process_tree.clone(),
+                )
+            };
+            let needed = needed || !self.session_senders.lock().unwrap().is_empty();
+            if !needed {
+                self.watcher = None;
+                if tree.is_none() {
+                    return;
+                }
+            }
+
+            // One snapshot feeds both the process tree and the watcher
+            let processes = match read_processes() {
+                Ok(processes) => processes,
+                Err(e) => {
+                    error!("Failed to read process table: {}", e);
+                    return;
+                }
+            };
+            if let Some(tree) = tree {
+                tree.update(&parent_table(&processes));
+            }
+            if !needed {
+                return;
+            }
+            let Some(watcher) = self.watcher.as_mut() else {
+                self.watcher = Some(ProcessWatcher::new(processes));
+                return;
+            };
+
+            let events = watcher.update(processes);
+            end_exited_sessions(&events, &self.session_tracker, &self.session_senders);
+            let send_events = {
+                let mut state = self.filter_state.write().unwrap();
+                for event in &events {
+                    if let MonitorEvent::ProcessExited { pid } = event {
+                        if state.config.watch_pids.contains(pid) {
+                            debug!("Watched PID {} exited", pid);
+                            state.exited_pids.insert(*pid);
+                        }
+                    }
+                }
+                state.config.process_events
+            };
+
+            if send_events {
+                for event in events {
+                    self.monitor_sender.send(event);
+                }
+            }
+        }
+    }
+
+    /// Ends the sessions of processes the process watcher saw exit. fs_usage
+    /// does not reliably print an `exit` record, so this is what closes
+    /// sessions of processes that exit with descriptors open.
+    fn end_exited_sessions(
+        events: &[MonitorEvent],
+        session_tracker: &Mutex<SessionTracker>,
+        session_senders: &Mutex<Vec<Sender<FileSession>>>,
+    ) {
+        for event in events {
+            if let MonitorEvent::ProcessExited { pid } = event {
+                let sessions = session_tracker.lock().unwrap().process_exited(*pid);
+                send_sessions(session_senders, &sessions);
+            }
+        }
+    }
+
+    /// Sends ended sessions to every `sessions()` receiver, dropping
+    /// receivers that have gone away.
+    fn send_sessions(session_senders: &Mutex<Vec<Sender<FileSession>>>, sessions: &[FileSession]) {
+        if sessions.is_empty() {
+            return;
+        }
+        session_senders.lock().unwrap().retain(|sender| {
+            sessions
+                .iter()
+                .all(|session| sender.send(session.clone()).is_ok())
+        });
+    }
+
+    /// Delivers monitor events to the `monitor_events()` channel and to
+    /// `activity()` receivers.
+    #[derive(Clone)]
+    struct MonitorEventSender {
+        sender: RingSender<MonitorEvent>,
+        subscribers: Subscribers,
+    }
+
+    impl MonitorEventSender {
+        fn send(&self, event: MonitorEvent) {
+            self.subscribers.publish_monitor_event(&event);
+            self.sender.send(event);
+        }
+    }
+
+    /// Delivers runtime errors to the `on_error` handlers and the `errors()`
+    /// channel.
+    struct ErrorReporter {
+        handlers: Vec<ErrorHandler>,
+        sender: RingSender<FsUsageError>,
+    }
+
+    impl ErrorReporter {
+        fn report(&self, error: FsUsageError) {
+            // Unparseable lines are routine in fs_usage output, so only
+            // failures of fs_usage itself are logged as errors
+            match &error {
+                FsUsageError::ParseError { .. } => debug!("{}", error),
+                FsUsageError::ChildExited { .. }
+                | FsUsageError::SpawnFailed(_)
+                | FsUsageError::NotRoot
+                | FsUsageError::KtraceBusy { .. } => error!("{}", error),
+                _ => warn!("{}", error),
+            }
+            for handler in &self.handlers {
+                handler(&error);
+            }
+            self.sender.send(error);
+        }
+    }
+
+    /// Upper bound on the fs_usage error output kept for `ChildExited`.
+    const MAX_STDERR_LEN: usize = 8192;
+
+    /// Drains fs_usage's stderr on a background thread so the pipe never
+    /// fills, keeping the most recent output.
+    struct StderrCapture {
+        output: Arc<Mutex<String>>,
+        reader: JoinHandle<()>,
+    }
+
+    impl StderrCapture {
+        fn new(stderr: ChildStderr) -> Self {
+            let output = Arc::new(Mutex::new(String::new()));
+            let reader = {
+                let output = output.clone();
+                thread::spawn(move || {
+                    for line in BufReader::new(stderr).lines().map_while(|line| line.ok()) {
+                        let mut output = output.lock().unwrap();
+                        output.push_str(&line);
+                        output.push('\n');
+                        if output.len() > MAX_STDERR_LEN {
+                            let mut cut = output.len() - MAX_STDERR_LEN;
+                            while !output.is_char_boundary(cut) {
+                                cut += 1;
+                            }
+                            output.drain(..cut);
+                        }
+                    }
+                })
+            };
+            Self { output, reader }
+        }
+
+        /// Waits for fs_usage to close stderr and returns what it wrote.
+        fn finish(self) -> String {
+            let _ = self.reader.join();
+            let output = self.output.lock().unwrap();
+            output.trim_end().to_string()
+        }
+    }
+
+    fn launch_fs_usage(args: &[String]) -> Result<(Child, ChildStdout, StderrCapture)> {
+        PreflightReport::check_environment()?;
+        spawn_fs_usage(args)
+    }
+
+    fn spawn_fs_usage(args: &[String]) -> Result<(Child, ChildStdout, StderrCapture)> {
+        let mut cmd = Command::new("fs_usage");
+        cmd.args(args);
+        info!("Starting fs_usage monitor with args: {:?}", cmd);
+        spawn_piped(cmd)
+    }
+
+    fn spawn_piped(mut cmd: Command) -> Result<(Child, ChildStdout, StderrCapture)> {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = cmd.spawn().map_err(FsUsageError::spawn_failed)?;
+
+        let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(FsUsageError::io(
+                "Failed to capture fs_usage output",
+                std::io::Error::other("missing pipe"),
+            ));
+        };
+        Ok((child, stdout, StderrCapture::new(stderr)))
+    }
+
+    /// Whether `line` starts with an fs_usage `HH:MM:SS` timestamp, i.e. is a
+    /// trace record rather than a header or diagnostic.
+    fn looks_like_trace_record(line: &str) -> bool {
+        line.split_whitespace().next().is_some_and(|token| {
+            let bytes = token.as_bytes();
+            bytes.len() >= 8
+                && bytes[..8].iter().enumerate().all(|(i, b)| match i {
+                    2 | 5 => *b == b':',
+                    _ => b.is_ascii_digit(),
+                })
+        })
+    }
+
+    /// Parses one line of fs_usage output. Events that only carry a file
+    /// descriptor are returned with an empty `path` so the reader can resolve
+    /// them through its fd table before dropping the rest.
     pub(super) fn parse_fs_usage_line(line: &str) -> Option<FsEvent> {
         // fs_usage format examples:
         // 23:52:52.781431  fstatat64              [  2]           [-2]/private/tmp/test123.txt                                                                                                                                          0.001226   touch.3523509
@@ -312,6 +1757,7 @@ mod macos_impl {
             // WrData format: timestamp WrData[A] D=0x... B=0x... /dev/disk... actual/path duration W process.pid
             let mut actual_path = None;
             let mut device_path_seen = false;
+            let mut bytes = None;
 
             for (i, part) in parts.iter().enumerate() {
                 if i < 2 || i >= parts.len() - 3 {
@@ -319,7 +1765,11 @@ mod macos_impl {
                 }
 
                 // Skip D= and B= parameters
-                if part.starts_with("D=") || part.starts_with("B=") {
+                if let Some(value) = part.strip_prefix("B=") {
+                    bytes = parse_byte_count(value);
+                    continue;
+                }
+                if part.starts_with("D=") {
                     continue;
                 }
 
@@ -349,6 +1799,8 @@ mod macos_impl {
                     operation,
                     path,
                     result: "OK".to_string(),
+                    bytes,
+                    ..Default::default()
                 });
             }
         }
@@ -356,6 +1808,9 @@ mod macos_impl {
         // Original parsing logic for non-WrData/RdData operations
         let mut path_parts = Vec::new();
         let mut found_path_start = false;
+        let mut fd = None;
+        let mut open_flags = None;
+        let mut bytes = None;
 
         for (i, part) in parts.iter().enumerate() {
             if i < 2 {
@@ -369,10 +1824,26 @@ mod macos_impl {
             if part.starts_with('[') && part.ends_with(']') {
                 continue;
             }
-            if part.starts_with("F=") || part.starts_with("B=") || part.starts_with("D=") {
+            if let Some(value) = part.strip_prefix("F=") {
+                fd = value.parse().ok();
+                continue;
+            }
+            if let Some(value) = part.strip_prefix("B=") {
+                bytes = parse_byte_count(value);
+                continue;
+            }
+            if part.starts_with("D=") {
                 continue;
             }
 
+            // Open mode like (R_____) or (_WC_T__)
+            if !found_path_start && crate::classifier::is_open_operation(&operation) {
+                if let Some(flags) = OpenFlags::parse(part) {
+                    open_flags = Some(flags);
+                    continue;
+                }
+            }
+
             // Skip single character flags like "W" or "R"
             if part.len() == 1 && (*part == "W" || *part == "R") {
                 continue;
@@ -385,38 +1856,17 @@ mod macos_impl {
             }
         }
 
-        // Skip events without file paths (just file descriptors)
-        if path_parts.is_empty() {
-            return None;
-        }
-
-        let path = path_parts
-            .join(" ")
-            .split("Err#")
-            .next()?
-            .trim()
-            .to_string();
-
-        // Clean up path - remove [-2] prefixes and normalize
-        let path = if path.starts_with("[-") {
-            path.split("]").nth(1)?.to_string()
-        } else {
-            path
-        };
+        let path_text = path_parts.join(" ");
+        let path_text = path_text.split("Err#").next()?.trim();
 
-        // Convert private/tmp to /tmp etc
-        let path = if path.starts_with("private/tmp") {
-            path.replace("private/tmp", "/tmp")
-        } else if path.starts_with("/private/tmp") {
-            path.replace("/private/tmp", "/tmp")
+        // rename, link and friends print the source followed by the destination
+        let (path, target_path) = if crate::classifier::is_two_path_operation(&operation) {
+            split_two_paths(path_text)
         } else {
-            path
+            (path_text, None)
         };
-
-        // Skip if path is empty after cleanup
-        if path.is_empty() {
-            return None;
-        }
+        let path = clean_path(path)?;
+        let target_path = target_path.and_then(clean_path);
 
         let result = if line.contains("Err#") {
             line.split("Err#")
@@ -435,10 +1885,67 @@ mod macos_impl {
             operation,
             path,
             result,
+            fd,
+            open_flags,
+            bytes,
+            target_path,
+            process_info: None,
+            actor: None,
         })
     }
 
-    fn should_send_event(event: &FsEvent, patterns: &[Pattern], config: &FsUsageConfig) -> bool {
+    fn clean_path(path: &str) -> Option<String> {
+        // Clean up path - remove [-2] prefixes and normalize
+        let path = if path.starts_with("[-") {
+            path.split("]").nth(1)?
+        } else {
+            path
+        };
+
+        // Convert private/tmp to /tmp etc
+        let path = if path.starts_with("private/tmp") {
+            path.replace("private/tmp", "/tmp")
+        } else if path.starts_with("/private/tmp") {
+            path.replace("/private/tmp", "/tmp")
+        } else {
+            path.to_string()
+        };
+        Some(path)
+    }
+
+    /// Splits the path text of a two-path operation into source and
+    /// destination. The destination starts at the first later absolute (or
+    /// `[-N]`-prefixed) path; two relative paths are only split when neither
+    /// contains spaces, otherwise the text is kept as a single path.
+    fn split_two_paths(text: &str) -> (&str, Option<&str>) {
+        let destination_start = text.match_indices(' ').map(|(i, _)| i + 1).find(|&i| {
+            let rest = &text[i..];
+            rest.starts_with('/') || rest.starts_with("[-")
+        });
+
+        match destination_start {
+            Some(start) => (text[..start - 1].trim_end(), Some(&text[start..])),
+            None => match text.split_once(' ') {
+                Some((source, destination)) if !destination.contains(' ') => {
+                    (source, Some(destination))
+                }
+                _ => (text, None),
+            },
+        }
+    }
+
+    /// Parses a `B=` value, which fs_usage prints in hex (`0xea`).
+    fn parse_byte_count(value: &str) -> Option<u64> {
+        match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    }
+
+    fn should_send_event(event: &FsEvent, state: &FilterState) -> bool {
+        let config = &state.config;
+        let process_tree = state.process_tree.as_deref();
+
         debug!(
             "Checking event: pid={}, operation={}, path={}",
             event.pid, event.operation, event.path
@@ -449,65 +1956,76 @@ mod macos_impl {
             return false;
         }
 
-        if !config.watch_pids.is_empty() && !config.watch_pids.contains(&event.pid) {
+        let watching_pids = !config.watch_pids.is_empty() || process_tree.is_some();
+        let watched_pid =
+            config.watch_pids.contains(&event.pid) && !state.exited_pids.contains(&event.pid);
+        if watching_pids
+            && !watched_pid
+            && !process_tree.is_some_and(|tree| tree.contains(event.pid))
+        {
             debug!("Event not in watch PIDs: {}", event.pid);
             return false;
         }
 
+        if !state.process_matchers.is_empty()
+            && !state
+                .process_matchers
+                .iter()
+                .any(|m| m.matches(&event.process_name))
+        {
+            debug!("Event not in watch processes: {}", event.process_name);
+            return false;
+        }
+
         // Check operation type filtering
         if !config.operation_types.contains(&OperationType::All) {
             let matches_operation = config
                 .operation_types
                 .iter()
-                .any(|op_type| op_type.matches_operation(&event.operation));
+                .any(|op_type| config.operation_classifier.matches_event(op_type, event));
             if !matches_operation {
                 debug!("Event operation '{}' not in allowed types", event.operation);
                 return false;
             }
         }
 
-        if config.watch_paths.is_empty() && patterns.is_empty() {
+        if !matches_watch_paths(event, state) {
+            return false;
+        }
+
+        matches_path_rules(event, state)
+    }
+
+    fn matches_watch_paths(event: &FsEvent, state: &FilterState) -> bool {
+        let config = &state.config;
+
+        if config.watch_paths.is_empty() && state.patterns.is_empty() {
             debug!("No watch paths or patterns, allowing event");
             return true;
         }
 
-        // If exact path matching is enabled, check direct path containment
-        if config.exact_path_matching && !config.watch_paths.is_empty() {
-            for watch_path in &config.watch_paths {
-                let abs_path = format!("{}/", watch_path.trim_end_matches('/'));
-                let rel_path = format!(
-                    "{}/",
-                    std::path::Path::new(watch_path)
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or(watch_path)
-                );
+        // Two-path operations match if either side does
+        event_paths(event).any(|path| matches_watch_path(path, state))
+    }
 
-                if event.path.contains(&abs_path) || event.path.contains(&rel_path) {
-                    debug!(
-                        "Exact match: path '{}' contains '{}' or '{}'",
-                        event.path, abs_path, rel_path
-                    );
-                    return true;
-                }
-            }
-            return false;
+    fn matches_watch_path(path: &str, state: &FilterState) -> bool {
+        // If exact path matching is enabled, check component-wise containment
+        if let Some(matcher) = &state.exact_path_matcher {
+            let matched = matcher.matches(path);
+            debug!("Exact match for path '{}': {}", path, matched);
+            return matched;
         }
 
         // Fall back to pattern matching
-        for pattern in patterns {
-            if pattern.matches(&event.path) {
-                debug!(
-                    "Pattern '{}' matches path '{}'",
-                    pattern.as_str(),
-                    event.path
-                );
+        for pattern in &state.patterns {
+            if pattern.matches(path) {
+                debug!("Pattern '{}' matches path '{}'", pattern.as_str(), path);
                 return true;
             } else {
                 debug!(
                     "Pattern '{}' does NOT match path '{}'",
                     pattern.as_str(),
-                    event.path
+                    path
                 );
             }
         }
@@ -515,6 +2033,40 @@ mod macos_impl {
         false
     }
 
+    /// The event's path followed by its `target_path`, if any.
+    fn event_paths(event: &FsEvent) -> impl Iterator<Item = &str> {
+        std::iter::once(event.path.as_str()).chain(event.target_path.as_deref())
+    }
+
+    fn matches_path_rules(event: &FsEvent, state: &FilterState) -> bool {
+        if state.rules.is_empty() {
+            return true;
+        }
+
+        let classifier = &state.config.operation_classifier;
+        let mut candidates = state.rules.iter().filter(|rule| rule.matches_target(event));
+        let matched = match state.config.rule_matching {
+            RuleMatching::FirstMatch => candidates
+                .next()
+                .is_some_and(|rule| rule.matches_operation(event, classifier)),
+            RuleMatching::AnyMatch => {
+                candidates.any(|rule| rule.matches_operation(event, classifier))
+            }
+        };
+
+        if !matched {
+            debug!(
+                "Event '{}' on '{}' not allowed by path rules",
+                event.operation, event.path
+            );
+        }
+        matched
+    }
+
+    fn passes_filters(event: &FsEvent, filters: &[EventFilter]) -> bool {
+        filters.iter().all(|filter| filter(event))
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -552,6 +2104,94 @@ mod macos_impl {
             assert_eq!(event.pid, 424229);
             assert_eq!(event.path, "test/test/fs_direct_test.txt");
             assert_eq!(event.result, "OK");
+            assert_eq!(event.bytes, Some(0x1000));
+        }
+
+        #[test]
+        fn test_parse_open_flags() {
+            let line = "10:14:02.529417  open              F=5        (R_____)  /usr/share/zoneinfo/UTC                                                                                                                                  0.000021   date.51023";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.operation, "open");
+            assert_eq!(event.path, "/usr/share/zoneinfo/UTC");
+            assert_eq!(event.fd, Some(5));
+            let flags = event.open_flags.unwrap();
+            assert!(flags.read && !flags.is_write());
+
+            let line = "10:14:02.530112  open              F=6        (_WC_T_)  /private/tmp/out.txt                                                                                                                                     0.000046   bash.51020";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.path, "/tmp/out.txt");
+            let flags = event.open_flags.unwrap();
+            assert!(flags.write && flags.create && flags.truncate);
+
+            // Descriptor-only lines keep the fd and an empty path
+            let line = "23:57:54.210609  write             F=6    B=0xea                                                                                                                                                                              0.000001   bash.51020";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.fd, Some(6));
+            assert!(event.path.is_empty());
+            assert!(event.open_flags.is_none());
+            assert_eq!(event.bytes, Some(0xea));
+        }
+
+        #[test]
+        fn test_parse_two_path_operations() {
+            let line = "10:20:31.118852  rename            /Users/me/src/.main.rs.tmp1234 /Users/me/src/main.rs                                                                    0.000089   vim.4242";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.path, "/Users/me/src/.main.rs.tmp1234");
+            assert_eq!(event.target_path.as_deref(), Some("/Users/me/src/main.rs"));
+
+            // Spaces in the source path
+            let line = "10:20:31.118852  rename            [-2]/private/tmp/my file.txt /private/tmp/b.txt                                                                      0.000089   mv.4243";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.path, "/tmp/my file.txt");
+            assert_eq!(event.target_path.as_deref(), Some("/tmp/b.txt"));
+
+            let line = "10:20:31.118852  symlink           private/tmp/a private/tmp/b                                                                                          0.000012   ln.4244";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.path, "/tmp/a");
+            assert_eq!(event.target_path.as_deref(), Some("/tmp/b"));
+
+            // Single-path operations keep spaces in the path
+            let line = "10:20:31.118852  unlink            /tmp/a b                                                                                                             0.000012   rm.4245";
+            let event = parse_fs_usage_line(line).unwrap();
+            assert_eq!(event.path, "/tmp/a b");
+            assert!(event.target_path.is_none());
+
+            // Path filters match either side
+            let into_repo = FsEvent {
+                operation: "rename".to_string(),
+                path: "/tmp/download.part".to_string(),
+                target_path: Some("/repo/src/lib.rs".to_string()),
+                ..Default::default()
+            };
+            for (watch_path, exact_path_matching) in [("/repo/**/*", false), ("/repo", true)] {
+                let config = FsUsageConfig {
+                    watch_paths: vec![watch_path.to_string()],
+                    exact_path_matching,
+                    ..Default::default()
+                };
+                let state = FilterState::new(config, None).unwrap();
+                assert!(should_send_event(&into_repo, &state));
+            }
+        }
+
+        #[test]
+        fn test_unparseable_trace_records() {
+            let truncated = "10:20:31.118852  open              F=4";
+            assert!(parse_fs_usage_line(truncated).is_none());
+            assert!(looks_like_trace_record(truncated));
+            assert!(!looks_like_trace_record(""));
+            assert!(!looks_like_trace_record(
+                "fs_usage: this program must be run as root"
+            ));
+
+            let config = FsUsageConfig {
+                watch_paths: vec!["/repo/[".to_string()],
+                ..Default::default()
+            };
+            assert!(matches!(
+                FsUsageMonitor::new(config),
+                Err(FsUsageError::InvalidPattern { pattern, .. }) if pattern == "/repo/["
+            ));
         }
 
         #[test]
@@ -580,20 +2220,481 @@ mod macos_impl {
             assert!(OperationType::Read.matches_operation("RdData"));
             assert!(!OperationType::Read.matches_operation("write"));
 
-            assert!(OperationType::Create.matches_operation("open"));
+            // Plain opens are only writes/creates when their mode says so
+            assert!(!OperationType::Create.matches_operation("open"));
+            assert!(OperationType::Open.matches_operation("open"));
             assert!(OperationType::Delete.matches_operation("unlink"));
             assert!(OperationType::Move.matches_operation("rename"));
             assert!(OperationType::Chmod.matches_operation("chmod"));
             assert!(OperationType::Chmod.matches_operation("chmod_extended"));
 
             assert!(OperationType::All.matches_operation("anything"));
+
+            // The operation filter honours decoded open flags
+            let writes_only = FilterState::new(
+                FsUsageConfig {
+                    operation_types: vec![OperationType::Write],
+                    ..Default::default()
+                },
+                None,
+            )
+            .unwrap();
+            let open = |mode: &str| FsEvent {
+                operation: "open".to_string(),
+                path: "/tmp/out.txt".to_string(),
+                open_flags: OpenFlags::parse(mode),
+                ..Default::default()
+            };
+            assert!(should_send_event(&open("(_WC_T_)"), &writes_only));
+            assert!(!should_send_event(&open("(R_____)"), &writes_only));
+        }
+
+        #[test]
+        fn test_custom_filters() {
+            let line = "21:35:08.701508    WrData[A]       D=0x1b3f3978  B=0x1000   /dev/disk3s5    test/test/fs_direct_test.txt      0.000303 W bash.424229";
+            let event = parse_fs_usage_line(line).unwrap();
+
+            assert!(passes_filters(&event, &[]));
+
+            let not_tmp: EventFilter = std::sync::Arc::new(|e: &FsEvent| !e.path.ends_with(".tmp"));
+            let only_bash: EventFilter =
+                std::sync::Arc::new(|e: &FsEvent| e.process_name == "bash");
+            assert!(passes_filters(&event, &[not_tmp.clone(), only_bash]));
+
+            let only_vim: EventFilter = std::sync::Arc::new(|e: &FsEvent| e.process_name == "vim");
+            assert!(!passes_filters(&event, &[not_tmp, only_vim]));
+        }
+
+        #[test]
+        fn test_watch_processes() {
+            let line = "23:52:52.781431  fstatat64              [  2]           [-2]/private/tmp/test123.txt                                                                                                                                          0.001226   touch.3523509";
+            let event = parse_fs_usage_line(line).unwrap();
+            let watching = |patterns: Vec<ProcessPattern>| {
+                let config = FsUsageConfig {
+                    watch_processes: patterns,
+                    ..Default::default()
+                };
+                should_send_event(&event, &FilterState::new(config, None).unwrap())
+            };
+
+            assert!(watching(vec![]));
+            assert!(watching(vec!["vim".into(), "touch".into()]));
+            assert!(!watching(vec!["vim".into(), "code".into()]));
+            assert!(watching(vec!["tou*".into()]));
+            // Plain names and globs are both case-sensitive
+            assert!(!watching(vec!["Touch".into()]));
+            assert!(!watching(vec!["Tou*".into()]));
+            assert!(watching(vec![ProcessPattern::regex("^(touch|cp)$")]));
+            assert!(!watching(vec![ProcessPattern::regex("^to$")]));
+
+            assert_eq!(
+                ProcessPattern::from("claude"),
+                ProcessPattern::Exact("claude".to_string())
+            );
+            assert_eq!(
+                ProcessPattern::from("node*"),
+                ProcessPattern::Glob("node*".to_string())
+            );
+        }
+
+        #[test]
+        fn test_exited_watch_pids() {
+            let event = FsEvent {
+                pid: 42,
+                operation: "write".to_string(),
+                path: "/tmp/a".to_string(),
+                ..Default::default()
+            };
+            let config = FsUsageConfig {
+                watch_pids: vec![42, 43],
+                ..Default::default()
+            };
+            let mut state = FilterState::new(config, None).unwrap();
+            assert!(should_send_event(&event, &state));
+
+            // Once 42 exits, a new process reusing the PID is not watched
+            state.exited_pids.insert(42);
+            assert!(!should_send_event(&event, &state));
+        }
+
+        #[test]
+        fn test_exited_pids_across_updates() {
+            let own_pid = std::process::id();
+            let dead_pid = 999_999;
+            let config = FsUsageConfig {
+                watch_pids: vec![own_pid, dead_pid],
+                ..Default::default()
+            };
+            let mut monitor = FsUsageMonitor::new(config.clone()).unwrap();
+            let exited = |monitor: &FsUsageMonitor| {
+                let mut pids: Vec<u32> = monitor
+                    .filter_state
+                    .read()
+                    .unwrap()
+                    .exited_pids
+                    .iter()
+                    .copied()
+                    .collect();
+                pids.sort_unstable();
+                pids
+            };
+
+            // A PID that was never running is marked without the poller
+            monitor.update_config(config.clone()).unwrap();
+            assert_eq!(exited(&monitor), vec![dead_pid]);
+
+            // The poller saw own_pid exit; removing and re-adding it clears
+            // the mark because the PID is running again
+            monitor
+                .filter_state
+                .write()
+                .unwrap()
+                .exited_pids
+                .insert(own_pid);
+            let mut removed = config.clone();
+            removed.watch_pids = vec![dead_pid];
+            monitor.update_config(removed).unwrap();
+            assert_eq!(exited(&monitor), vec![dead_pid]);
+            monitor.update_config(config).unwrap();
+            assert_eq!(exited(&monitor), vec![dead_pid]);
+        }
+
+        #[test]
+        fn test_sessions_end_on_process_exit() {
+            let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+            let (monitor_sender, monitor_receiver) = bounded(STATUS_CHANNEL_CAPACITY);
+            let (session_sender, sessions) = unbounded();
+            let mut poller = ProcessPoller {
+                watcher: None,
+                filter_state: Arc::new(RwLock::new(
+                    FilterState::new(FsUsageConfig::default(), None).unwrap(),
+                )),
+                monitor_sender: MonitorEventSender {
+                    sender: RingSender::new(monitor_sender, monitor_receiver),
+                    subscribers: Subscribers::new(
+                        None,
+                        BackpressurePolicy::Block,
+                        Arc::new(AtomicU64::new(0)),
+                    ),
+                },
+                session_tracker: Arc::new(Mutex::new(SessionTracker::default())),
+                session_senders: Arc::new(Mutex::new(vec![session_sender])),
+            };
+
+            // fs_usage printed the open but no exit record
+            let open = FsEvent {
+                timestamp: "10:00:00.000000".to_string(),
+                pid: child.id(),
+                operation: "open".to_string(),
+                path: "/tmp/log".to_string(),
+                result: "OK".to_string(),
+                fd: Some(3),
+                ..Default::default()
+            };
+            poller.session_tracker.lock().unwrap().observe(
+                &open,
+                true,
+                &OperationClassifier::default(),
+            );
+            poller.poll();
+            assert!(sessions.try_recv().is_err());
+
+            child.kill().unwrap();
+            child.wait().unwrap();
+            poller.poll();
+            let session = sessions.try_recv().unwrap();
+            assert_eq!(session.path, "/tmp/log");
+            assert_eq!(session.end, SessionEnd::ProcessExited);
+        }
+
+        #[test]
+        fn test_command_tree_scoping() {
+            // The tree spawn_command builds around a command with PID 4242
+            let tree = Arc::new(ProcessTree::new(vec![4242]));
+            tree.update(&[(1, 0), (4242, 1), (4250, 4242), (4260, 4250), (4300, 1)]);
+            let state = FilterState::new(FsUsageConfig::default(), Some(tree)).unwrap();
+
+            let event = |pid| FsEvent {
+                pid,
+                operation: "write".to_string(),
+                path: "/tmp/a".to_string(),
+                ..Default::default()
+            };
+            assert!(should_send_event(&event(4242), &state));
+            assert!(should_send_event(&event(4260), &state));
+            assert!(!should_send_event(&event(4300), &state));
+            assert!(!should_send_event(&event(1), &state));
+        }
+
+        #[test]
+        fn test_exact_path_matching() {
+            let line = "23:52:52.781431  fstatat64              [  2]           [-2]/private/tmp/project/test123.txt                                                                                                                                          0.001226   touch.3523509";
+            let event = parse_fs_usage_line(line).unwrap();
+
+            let matches = |watch_path: &str| {
+                let config = FsUsageConfig {
+                    watch_paths: vec![watch_path.to_string()],
+                    exact_path_matching: true,
+                    ..Default::default()
+                };
+                should_send_event(&event, &FilterState::new(config, None).unwrap())
+            };
+
+            assert!(matches("/tmp/project"));
+            assert!(matches("/tmp/project/"));
+            assert!(!matches("/tmp/proj"));
+            assert!(!matches("/other/tmp/project"));
+        }
+
+        #[test]
+        fn test_path_rules() {
+            let event = |operation: &str, path: &str, process_name: &str| FsEvent {
+                timestamp: "12:00:00.000000".to_string(),
+                process_name: process_name.to_string(),
+                pid: 100,
+                operation: operation.to_string(),
+                path: path.to_string(),
+                result: "OK".to_string(),
+                ..Default::default()
+            };
+
+            let state = |rule_matching: RuleMatching| {
+                let config = FsUsageConfig {
+                    path_rules: vec![
+                        PathRule::new("/repo/target/**").operations([]),
+                        PathRule::new("/Users/me/.ssh/**").operations([OperationType::Read]),
+                        PathRule::new("/repo/**").operations([OperationType::Write]),
+                        PathRule::new("/repo/**")
+                            .operations([OperationType::Read])
+                            .process("vim"),
+                    ],
+                    rule_matching,
+                    ..Default::default()
+                };
+                FilterState::new(config, None).unwrap()
+            };
+
+            let first = state(RuleMatching::FirstMatch);
+            assert!(should_send_event(
+                &event("read", "/Users/me/.ssh/id_rsa", "ssh"),
+                &first
+            ));
+            assert!(!should_send_event(
+                &event("write", "/Users/me/.ssh/id_rsa", "ssh"),
+                &first
+            ));
+            assert!(should_send_event(
+                &event("write", "/repo/src/main.rs", "vim"),
+                &first
+            ));
+            assert!(!should_send_event(
+                &event("write", "/repo/target/debug/app", "cargo"),
+                &first
+            ));
+            assert!(!should_send_event(
+                &event("write", "/etc/hosts", "vim"),
+                &first
+            ));
+            // The first /repo rule wins, so the vim read rule is never consulted
+            assert!(!should_send_event(
+                &event("read", "/repo/src/main.rs", "vim"),
+                &first
+            ));
+
+            let any = state(RuleMatching::AnyMatch);
+            assert!(should_send_event(
+                &event("read", "/repo/src/main.rs", "vim"),
+                &any
+            ));
+            assert!(!should_send_event(
+                &event("read", "/repo/src/main.rs", "code"),
+                &any
+            ));
+            assert!(should_send_event(
+                &event("write", "/repo/target/debug/app", "cargo"),
+                &any
+            ));
+        }
+
+        #[test]
+        fn test_run_command_with_handler() {
+            // Stands in for fs_usage: an unrelated record, then one for the
+            // command once it has written its PID
+            const FAKE_FS_USAGE: &str = r#"
+                pid_file=/tmp/fs_usage_sys_run_command.$PPID
+                echo "10:00:00.000000  open  F=3  (R_____)  /etc/hosts  0.000010   launchd.1"
+                while [ ! -s "$pid_file" ]; do sleep 0.01; done
+                echo "10:00:00.100000  open  F=3  (_WC_T_)  /tmp/out.txt  0.000010   sh.$(cat "$pid_file")"
+                exec sleep 30
+            "#;
+
+            let handled = Arc::new(AtomicU64::new(0));
+            let counter = handled.clone();
+            let mut monitor = crate::FsUsageMonitorBuilder::new()
+                .on_event(move |_| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                })
+                .build()
+                .unwrap();
+            monitor.launch = |_| {
+                let mut fake = Command::new("sh");
+                fake.args(["-c", FAKE_FS_USAGE]);
+                spawn_piped(fake)
+            };
+
+            let mut command = Command::new("sh");
+            command.args([
+                "-c",
+                "echo $$ > /tmp/fs_usage_sys_run_command.$PPID; sleep 0.2",
+            ]);
+            let output = monitor.run_command(command).unwrap();
+            let _ = std::fs::remove_file(format!(
+                "/tmp/fs_usage_sys_run_command.{}",
+                std::process::id()
+            ));
+
+            assert!(output.status.success());
+            let paths: Vec<_> = output.events.iter().map(|e| e.path.as_str()).collect();
+            assert_eq!(paths, vec!["/tmp/out.txt"]);
+            assert_eq!(handled.load(Ordering::Relaxed), 1);
+        }
+
+        #[tokio::test]
+        async fn test_run_until_rejects_async_context() {
+            let mut monitor = FsUsageMonitor::new(FsUsageConfig::default()).unwrap();
+            assert!(monitor.run_until(std::future::ready(())).is_err());
+            assert!(!monitor.is_running());
+        }
+
+        #[test]
+        fn test_stop_during_restart_backoff() {
+            let config = FsUsageConfig {
+                restart_policy: Some(RestartPolicy {
+                    initial_backoff: Duration::from_secs(60),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let mut monitor = FsUsageMonitor::new(config).unwrap();
+            // Stands in for an fs_usage that exits straight away
+            monitor.launch = |_| spawn_piped(Command::new("true"));
+            monitor.start().unwrap();
+            assert!(matches!(
+                monitor.errors().recv_timeout(Duration::from_secs(5)),
+                Ok(FsUsageError::ChildExited { .. })
+            ));
+
+            // The supervisor is now waiting 60s before restarting
+            let stopping = Instant::now();
+            monitor.stop().unwrap();
+            assert!(stopping.elapsed() < Duration::from_secs(5));
+            assert!(!monitor.is_running());
+            assert_eq!(monitor.restart_count(), 0);
+        }
+
+        #[test]
+        fn test_stop_ends_derived_feeds() {
+            let mut monitor = FsUsageMonitor::new(FsUsageConfig::default()).unwrap();
+            let batches = monitor.batches(10, Duration::from_secs(60));
+            let changes = monitor.debounce(Duration::from_secs(60));
+            let logical = monitor.logical_changes();
+
+            monitor.stop().unwrap();
+            assert!(monitor.helpers.lock().unwrap().is_empty());
+            assert!(batches.recv().is_err());
+            assert!(changes.recv().is_err());
+            assert!(logical.recv().is_err());
+        }
+
+        #[test]
+        fn test_update_config_without_restart() {
+            let mut monitor = FsUsageMonitor::new(FsUsageConfig::default()).unwrap();
+            let args = monitor.filter_state.read().unwrap().fs_usage_args();
+
+            monitor.add_watch_path("/tmp/**/*").unwrap();
+            monitor.add_watch_path("/repo/**/*").unwrap();
+            assert_eq!(
+                monitor.config().watch_paths,
+                vec!["/tmp/**/*", "/repo/**/*"]
+            );
+            assert!(monitor.remove_watch_path("/tmp/**/*").unwrap());
+            assert!(!monitor.remove_watch_path("/tmp/**/*").unwrap());
+            assert_eq!(monitor.config().watch_paths, vec!["/repo/**/*"]);
+
+            // Path filters are applied in-process, so fs_usage args are unchanged
+            assert_eq!(monitor.filter_state.read().unwrap().fs_usage_args(), args);
+
+            let mut config = monitor.config();
+            config.exclude_processes.push("Spotlight".to_string());
+            monitor.update_config(config).unwrap();
+            assert_ne!(monitor.filter_state.read().unwrap().fs_usage_args(), args);
+
+            // Invalid patterns are rejected and the previous config is kept
+            assert!(monitor.add_watch_path("[").is_err());
+            assert_eq!(monitor.config().watch_paths, vec!["/repo/**/*"]);
+
+            let mut config = monitor.config();
+            config.channel_capacity = Some(10);
+            assert!(matches!(
+                monitor.update_config(config),
+                Err(FsUsageError::InvalidState(_))
+            ));
+        }
+
+        #[test]
+        fn test_update_config_restores_on_failed_restart() {
+            let mut monitor = FsUsageMonitor::new(FsUsageConfig::default()).unwrap();
+            // fs_usage cannot be launched with -p
+            monitor.launch = |args| {
+                if args.iter().any(|arg| arg == "-p") {
+                    return Err(FsUsageError::InvalidState("launch failed".to_string()));
+                }
+                let mut fake = Command::new("sleep");
+                fake.arg("30");
+                spawn_piped(fake)
+            };
+            monitor.start().unwrap();
+
+            let mut config = monitor.config();
+            config.watch_pids = vec![std::process::id()];
+            assert!(monitor.update_config(config).is_err());
+            assert!(monitor.config().watch_pids.is_empty());
+            assert!(monitor.is_running());
+
+            let mut config = monitor.config();
+            config.events_channel = false;
+            assert!(matches!(
+                monitor.update_config(config),
+                Err(FsUsageError::InvalidState(_))
+            ));
+            monitor.stop().unwrap();
         }
     }
 }
 
 // Re-export macOS implementation
 #[cfg(target_os = "macos")]
-pub use macos_impl::{FsUsageConfig, FsUsageMonitor};
+pub use atomic_save::LogicalChange;
+#[cfg(target_os = "macos")]
+pub use backpressure::BackpressurePolicy;
+#[cfg(target_os = "macos")]
+pub use classifier::OperationClassifier;
+#[cfg(target_os = "macos")]
+pub use debounce::FileChange;
+#[cfg(target_os = "macos")]
+pub use macos_impl::{CommandOutput, FsUsageConfig, FsUsageMonitor};
+#[cfg(target_os = "macos")]
+pub use preflight::{Capabilities, PreflightReport};
+#[cfg(target_os = "macos")]
+pub use rate_limit::{RateLimit, RateLimitConfig};
+#[cfg(target_os = "macos")]
+pub use session::{FileSession, SessionEnd};
+#[cfg(target_os = "macos")]
+pub use stream::EventStream;
+#[cfg(target_os = "macos")]
+pub use subscription::Subscription;
+#[cfg(target_os = "macos")]
+pub use supervisor::RestartPolicy;
 
 // Provide stubs for non-macOS platforms
 #[cfg(not(target_os = "macos"))]
@@ -0,0 +1,163 @@
+/// Component-wise path containment used by `exact_path_matching`.
+///
+/// A watch path matches an event path when every component of the watch path
+/// is a leading component of the event path, so `/a/project` matches
+/// `/a/project` and `/a/project/src/main.rs` but not `/a/project2` or
+/// `/b/other/project/x`.
+///
+/// fs_usage frequently prints paths without a leading `/` (relative to the
+/// volume the file lives on). Those are resolved against each of the
+/// configured roots before matching. When `match_relative_tails` is enabled a
+/// relative path also matches if it starts with a trailing portion of a watch
+/// path, e.g. `project/src/main.rs` for `/a/project`.
+#[derive(Debug, Clone)]
+pub(crate) struct ExactPathMatcher {
+    watch_paths: Vec<Vec<String>>,
+    roots: Vec<Vec<String>>,
+    match_relative_tails: bool,
+}
+
+impl ExactPathMatcher {
+    pub(crate) fn new(
+        watch_paths: &[String],
+        roots: &[String],
+        match_relative_tails: bool,
+    ) -> Self {
+        Self {
+            watch_paths: watch_paths.iter().map(|p| components(p)).collect(),
+            roots: roots.iter().map(|r| components(r)).collect(),
+            match_relative_tails,
+        }
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        let event = components(path);
+        if event.is_empty() {
+            return false;
+        }
+
+        if path.starts_with('/') {
+            return self
+                .watch_paths
+                .iter()
+                .any(|watch| event.starts_with(watch));
+        }
+
+        for root in &self.roots {
+            let resolved: Vec<String> = root.iter().chain(event.iter()).cloned().collect();
+            if self
+                .watch_paths
+                .iter()
+                .any(|watch| resolved.starts_with(watch))
+            {
+                return true;
+            }
+        }
+
+        self.match_relative_tails
+            && self
+                .watch_paths
+                .iter()
+                .any(|watch| (0..watch.len()).any(|start| event.starts_with(&watch[start..])))
+    }
+}
+
+/// Reduces the different spellings fs_usage uses for one file to a single
+/// key: absolute paths have the longest matching root (such as
+/// `/System/Volumes/Data`) stripped, and volume-relative paths are taken as
+/// they are, so `/Users/me/a.rs`, `Users/me/a.rs` and
+/// `/System/Volumes/Data/Users/me/a.rs` compare equal.
+#[derive(Debug, Clone)]
+pub(crate) struct PathKeys {
+    roots: Vec<Vec<String>>,
+}
+
+impl PathKeys {
+    pub(crate) fn new(roots: &[String]) -> Self {
+        let mut roots: Vec<Vec<String>> = roots.iter().map(|r| components(r)).collect();
+        roots.sort_by_key(|root| std::cmp::Reverse(root.len()));
+        Self { roots }
+    }
+
+    pub(crate) fn key(&self, path: &str) -> String {
+        let mut components = components(path);
+        if path.starts_with('/') {
+            if let Some(root) = self.roots.iter().find(|root| components.starts_with(root)) {
+                components.drain(..root.len());
+            }
+        }
+        components.join("/")
+    }
+}
+
+/// Splits a path into normalized components, dropping empty and `.` segments
+/// and resolving `..` lexically.
+fn components(path: &str) -> Vec<String> {
+    let mut components: Vec<String> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            part => components.push(part.to_string()),
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(watch: &[&str], tails: bool) -> ExactPathMatcher {
+        let watch: Vec<String> = watch.iter().map(|p| p.to_string()).collect();
+        let roots = vec!["/".to_string(), "/System/Volumes/Data".to_string()];
+        ExactPathMatcher::new(&watch, &roots, tails)
+    }
+
+    #[test]
+    fn test_exact_path_matrix() {
+        let cases = [
+            // (watch, event, tails, expected)
+            ("/a/project", "/a/project", false, true),
+            ("/a/project", "/a/project/src/main.rs", false, true),
+            ("/a/project/", "/a/project/src/main.rs", false, true),
+            ("/a/project", "/a/project2/main.rs", false, false),
+            ("/a/project", "/b/other/project/x", false, false),
+            ("/a/project", "/a", false, false),
+            ("/a/project", "/a/./project/../project/x", false, true),
+            // Relative paths resolved against roots
+            ("/a/project", "a/project/x", false, true),
+            ("/System/Volumes/Data/a/project", "a/project/x", false, true),
+            ("/a/project", "b/project/x", false, false),
+            // Relative tails
+            ("/a/project", "project/x", false, false),
+            ("/a/project", "project/x", true, true),
+            ("/a/project", "other/project/x", true, false),
+            ("/a/project", "projectx/y", true, false),
+            ("/a/project", "/b/other/project/x", true, false),
+            ("/Users/me/code/app", "code/app/src/lib.rs", true, true),
+        ];
+
+        for (watch, event, tails, expected) in cases {
+            assert_eq!(
+                matcher(&[watch], tails).matches(event),
+                expected,
+                "watch={} event={} tails={}",
+                watch,
+                event,
+                tails
+            );
+        }
+    }
+
+    #[test]
+    fn test_multiple_watch_paths() {
+        let m = matcher(&["/a/project", "/tmp/work"], false);
+        assert!(m.matches("/tmp/work/file.txt"));
+        assert!(m.matches("/a/project/file.txt"));
+        assert!(!m.matches("/tmp/workspace/file.txt"));
+        assert!(!m.matches(""));
+    }
+}
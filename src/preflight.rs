@@ -0,0 +1,219 @@
+use crate::error::{FsUsageError, Result};
+use crate::process_info::ProcessInfo;
+use crate::process_watch::{read_processes, ProcessEntry};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Tools that hold the kernel trace facility while they run. Only one ktrace
+/// session can be active, so fs_usage fails with "Resource busy" next to them.
+const KTRACE_TOOLS: &[&str] = &["fs_usage", "sc_usage", "latency", "ktrace"];
+
+/// `ktrace` subcommands that start a trace session; others such as
+/// `ktrace info` only inspect it.
+const KTRACE_SESSION_COMMANDS: &[&str] = &["trace", "artrace", "record"];
+
+/// Whether the environment can run fs_usage, as reported by
+/// `FsUsageMonitor::preflight()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreflightReport {
+    /// Where `fs_usage` was found on `PATH`.
+    pub binary: Option<PathBuf>,
+    /// macOS version, e.g. "14.4.1". fs_usage ships with the OS and has no
+    /// version flag of its own.
+    pub os_version: Option<String>,
+    pub running_as_root: bool,
+    /// A process already holding the ktrace session, as (pid, name).
+    pub ktrace_session: Option<(u32, String)>,
+    pub capabilities: Capabilities,
+}
+
+/// What the fs_usage backend can do in this environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// File system calls can be traced: fs_usage is present, running as root
+    /// and no other ktrace session is active.
+    pub file_tracing: bool,
+    /// `ProcessInfo` lookups work, for `enrich_process_info()` and attribution.
+    pub process_details: bool,
+    /// Arguments and environment of other users' processes are readable,
+    /// which needs root.
+    pub other_users_process_details: bool,
+}
+
+impl PreflightReport {
+    pub(crate) fn run() -> Self {
+        let binary = find_in_path("fs_usage");
+        let running_as_root = is_root();
+        let ktrace_session = find_ktrace_session();
+        let process_details = ProcessInfo::lookup(std::process::id()).is_some();
+
+        Self {
+            capabilities: Capabilities {
+                file_tracing: binary.is_some() && running_as_root && ktrace_session.is_none(),
+                process_details,
+                other_users_process_details: process_details && running_as_root,
+            },
+            binary,
+            os_version: os_version(),
+            running_as_root,
+            ktrace_session,
+        }
+    }
+
+    /// The error `start()` fails with, if fs_usage cannot trace.
+    pub fn check(&self) -> Result<()> {
+        check(
+            self.binary.is_some(),
+            self.running_as_root,
+            self.ktrace_session.clone(),
+        )
+    }
+
+    /// The checks `start()` and each restart run: `check()` without the
+    /// process lookup and OS version, which are only informational.
+    pub(crate) fn check_environment() -> Result<()> {
+        check(
+            find_in_path("fs_usage").is_some(),
+            is_root(),
+            find_ktrace_session(),
+        )
+    }
+}
+
+fn check(binary_found: bool, running_as_root: bool, holder: Option<(u32, String)>) -> Result<()> {
+    if !binary_found {
+        return Err(FsUsageError::BinaryMissing);
+    }
+    if !running_as_root {
+        return Err(FsUsageError::NotRoot);
+    }
+    if holder.is_some() {
+        return Err(FsUsageError::KtraceBusy { holder });
+    }
+    Ok(())
+}
+
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Another process holding the ktrace session, as (pid, name).
+pub(crate) fn find_ktrace_session() -> Option<(u32, String)> {
+    ktrace_holder(read_processes().ok()?, std::process::id())
+}
+
+fn ktrace_holder(processes: HashMap<u32, ProcessEntry>, own_pid: u32) -> Option<(u32, String)> {
+    let mut sessions: Vec<_> = processes
+        .into_iter()
+        .filter(|(pid, entry)| *pid != own_pid && holds_ktrace(entry))
+        .map(|(pid, entry)| (pid, entry.name()))
+        .collect();
+    sessions.sort_unstable();
+    sessions.into_iter().next()
+}
+
+fn holds_ktrace(entry: &ProcessEntry) -> bool {
+    let name = entry.name();
+    if entry.is_zombie() || !KTRACE_TOOLS.contains(&name.as_str()) {
+        return false;
+    }
+    name != "ktrace"
+        || entry
+            .argv
+            .get(1)
+            .is_some_and(|command| KTRACE_SESSION_COMMANDS.contains(&command.as_str()))
+}
+
+fn os_version() -> Option<String> {
+    let mut buffer = [0u8; 64];
+    let mut size = buffer.len();
+    let ret = unsafe {
+        libc::sysctlbyname(
+            c"kern.osproductversion".as_ptr(),
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return None;
+    }
+    let version = CStr::from_bytes_until_nul(&buffer[..size]).ok()?;
+    Some(version.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_order() {
+        let mut report = PreflightReport {
+            binary: None,
+            os_version: None,
+            running_as_root: false,
+            ktrace_session: Some((42, "fs_usage".to_string())),
+            capabilities: Capabilities {
+                file_tracing: false,
+                process_details: true,
+                other_users_process_details: false,
+            },
+        };
+        assert!(matches!(report.check(), Err(FsUsageError::BinaryMissing)));
+
+        report.binary = Some(PathBuf::from("/usr/bin/fs_usage"));
+        assert!(matches!(report.check(), Err(FsUsageError::NotRoot)));
+
+        report.running_as_root = true;
+        assert_eq!(
+            report.check().unwrap_err().to_string(),
+            "Another ktrace session is active (fs_usage, pid 42)"
+        );
+
+        report.ktrace_session = None;
+        assert!(report.check().is_ok());
+    }
+
+    #[test]
+    fn test_ktrace_holder() {
+        let entry = |state: &str, argv: &[&str]| ProcessEntry {
+            ppid: 1,
+            state: state.to_string(),
+            started: "Sat Oct 18 10:00:00 2026".to_string(),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+        };
+        let mut processes = HashMap::from([
+            (10, entry("S", &["/usr/bin/ktrace", "info"])),
+            (11, entry("Z", &["fs_usage"])),
+            (12, entry("S+", &["/usr/bin/fs_usage", "-w"])),
+            (13, entry("Ss", &["vim", "fs_usage.rs"])),
+        ]);
+        // The calling process itself is never reported
+        assert_eq!(ktrace_holder(processes.clone(), 12), None);
+        assert_eq!(
+            ktrace_holder(processes.clone(), 1),
+            Some((12, "fs_usage".to_string()))
+        );
+
+        processes.insert(9, entry("R", &["ktrace", "trace", "-f", "x"]));
+        assert_eq!(
+            ktrace_holder(processes, 12),
+            Some((9, "ktrace".to_string()))
+        );
+    }
+}
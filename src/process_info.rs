@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Upper bound on cached processes, cleared when exceeded.
+const MAX_CACHED_PROCESSES: usize = 4096;
+
+/// How long a cache entry is trusted before its start time is re-checked.
+const REVALIDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Details about a process, looked up from the OS (`proc_pidinfo`,
+/// `proc_pidpath` and `sysctl` on macOS, `/proc` elsewhere).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    /// Full path of the executable.
+    pub exe: Option<String>,
+    pub argv: Vec<String>,
+    pub uid: Option<u32>,
+    /// Working directory at the time of the lookup.
+    pub cwd: Option<String>,
+    /// Opaque process start time, used to tell a reused PID apart.
+    pub start_time: u64,
+}
+
+impl ProcessInfo {
+    /// Looks up `pid`. Returns `None` if the process does not exist or cannot
+    /// be inspected. Fields the OS refuses to report (e.g. `argv` of another
+    /// user's process without root) are left empty.
+    pub fn lookup(pid: u32) -> Option<Self> {
+        let (ppid, uid, start_time) = sys::basic_info(pid)?;
+        Some(Self {
+            pid,
+            ppid,
+            exe: sys::executable(pid),
+            argv: sys::arguments(pid).unwrap_or_default(),
+            uid,
+            cwd: sys::working_directory(pid),
+            start_time,
+        })
+    }
+}
+
+/// Parent of `pid`, or `None` if the process is gone.
+pub(crate) fn parent_pid(pid: u32) -> Option<u32> {
+    sys::basic_info(pid).map(|(ppid, _, _)| ppid)
+}
+
+struct CacheEntry {
+    info: ProcessInfo,
+    /// Looked up on first use, since only attribution rules need it.
+    environment: Option<Vec<String>>,
+    validated: Instant,
+}
+
+/// Caches `ProcessInfo` lookups. Entries older than a second are re-checked
+/// against the process start time, so a PID reused by a new process is
+/// looked up again instead of reporting the old process.
+#[derive(Default)]
+pub(crate) struct ProcessInfoCache {
+    entries: HashMap<u32, CacheEntry>,
+}
+
+impl ProcessInfoCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn get(&mut self, pid: u32) -> Option<&ProcessInfo> {
+        let now = Instant::now();
+        let fresh = match self.entries.get_mut(&pid) {
+            Some(entry) if now.duration_since(entry.validated) < REVALIDATE_INTERVAL => true,
+            Some(entry) => {
+                let same_process = sys::basic_info(pid)
+                    .is_some_and(|(_, _, start_time)| start_time == entry.info.start_time);
+                if same_process {
+                    entry.validated = now;
+                }
+                same_process
+            }
+            None => false,
+        };
+
+        if !fresh {
+            self.entries.remove(&pid);
+            let info = ProcessInfo::lookup(pid)?;
+            if self.entries.len() >= MAX_CACHED_PROCESSES {
+                self.entries.clear();
+            }
+            self.entries.insert(
+                pid,
+                CacheEntry {
+                    info,
+                    environment: None,
+                    validated: now,
+                },
+            );
+        }
+        self.entries.get(&pid).map(|entry| &entry.info)
+    }
+
+    /// `KEY=VALUE` environment entries of `pid`, cached with its other
+    /// details. Empty if the OS refuses to report them, which for other users'
+    /// processes is the case without root.
+    pub(crate) fn environment(&mut self, pid: u32) -> Option<&[String]> {
+        self.get(pid)?;
+        let entry = self.entries.get_mut(&pid)?;
+        let environment = entry
+            .environment
+            .get_or_insert_with(|| sys::environment(pid).unwrap_or_default());
+        Some(environment)
+    }
+
+    /// Drops the entry for `pid`, e.g. after it exited.
+    pub(crate) fn invalidate(&mut self, pid: u32) {
+        self.entries.remove(&pid);
+    }
+}
+
+/// Parses a `KERN_PROCARGS2` buffer into the arguments and environment:
+/// `argc`, the executable path, padding NULs, then `argc` NUL-terminated
+/// arguments followed by the NUL-terminated environment entries.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_procargs2(buffer: &[u8]) -> Option<(Vec<String>, Vec<String>)> {
+    let argc = usize::try_from(i32::from_ne_bytes(buffer.get(..4)?.try_into().ok()?)).ok()?;
+    let rest = &buffer[4..];
+    let exec_path_end = rest.iter().position(|&b| b == 0)?;
+    let args_start = exec_path_end + rest[exec_path_end..].iter().position(|&b| b != 0)?;
+
+    let mut strings = rest[args_start..]
+        .split(|&b| b == 0)
+        .map(|arg| String::from_utf8_lossy(arg).into_owned());
+    let argv = strings.by_ref().take(argc).collect();
+    // The environment ends at the first empty string
+    let environment = strings.take_while(|entry| !entry.is_empty()).collect();
+    Some((argv, environment))
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::cell::RefCell;
+    use std::ffi::CStr;
+    use std::mem;
+    use std::sync::OnceLock;
+
+    /// Parent PID, effective UID and start time from `proc_bsdinfo`.
+    pub(super) fn basic_info(pid: u32) -> Option<(u32, Option<u32>, u64)> {
+        let mut info: libc::proc_bsdinfo = unsafe { mem::zeroed() };
+        let size = mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+        let written = unsafe {
+            libc::proc_pidinfo(
+                pid as libc::c_int,
+                libc::PROC_PIDTBSDINFO,
+                0,
+                &mut info as *mut _ as *mut libc::c_void,
+                size,
+            )
+        };
+        if written != size {
+            return None;
+        }
+        let start_time = info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec;
+        Some((info.pbi_ppid, Some(info.pbi_uid), start_time))
+    }
+
+    pub(super) fn executable(pid: u32) -> Option<String> {
+        let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+        let len = unsafe {
+            libc::proc_pidpath(
+                pid as libc::c_int,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len() as u32,
+            )
+        };
+        if len <= 0 {
+            return None;
+        }
+        buffer.truncate(len as usize);
+        String::from_utf8(buffer).ok()
+    }
+
+    thread_local! {
+        /// `KERN_PROCARGS2` buffer, reused across lookups since it is
+        /// `ARG_MAX` bytes (about 1MB).
+        static PROCARGS_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn arg_max() -> Option<usize> {
+        static ARG_MAX: OnceLock<Option<usize>> = OnceLock::new();
+        *ARG_MAX.get_or_init(|| {
+            let mut arg_max: libc::c_int = 0;
+            let mut size = mem::size_of::<libc::c_int>();
+            let mut mib = [libc::CTL_KERN, libc::KERN_ARGMAX];
+            let ret = unsafe {
+                libc::sysctl(
+                    mib.as_mut_ptr(),
+                    mib.len() as libc::c_uint,
+                    &mut arg_max as *mut _ as *mut libc::c_void,
+                    &mut size,
+                    std::ptr::null_mut(),
+                    0,
+                )
+            };
+            (ret == 0 && arg_max > 0).then_some(arg_max as usize)
+        })
+    }
+
+    fn procargs2(pid: u32) -> Option<(Vec<String>, Vec<String>)> {
+        let arg_max = arg_max()?;
+        PROCARGS_BUFFER.with_borrow_mut(|buffer| {
+            buffer.resize(arg_max, 0);
+            let mut size = buffer.len();
+            let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid as libc::c_int];
+            let ret = unsafe {
+                libc::sysctl(
+                    mib.as_mut_ptr(),
+                    mib.len() as libc::c_uint,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    &mut size,
+                    std::ptr::null_mut(),
+                    0,
+                )
+            };
+            if ret != 0 {
+                return None;
+            }
+            super::parse_procargs2(&buffer[..size])
+        })
+    }
+
+    pub(super) fn arguments(pid: u32) -> Option<Vec<String>> {
+        procargs2(pid).map(|(argv, _)| argv)
+    }
+
+    pub(super) fn environment(pid: u32) -> Option<Vec<String>> {
+        procargs2(pid).map(|(_, environment)| environment)
+    }
+
+    pub(super) fn working_directory(pid: u32) -> Option<String> {
+        let mut info: libc::proc_vnodepathinfo = unsafe { mem::zeroed() };
+        let size = mem::size_of::<libc::proc_vnodepathinfo>() as libc::c_int;
+        let written = unsafe {
+            libc::proc_pidinfo(
+                pid as libc::c_int,
+                libc::PROC_PIDVNODEPATHINFO,
+                0,
+                &mut info as *mut _ as *mut libc::c_void,
+                size,
+            )
+        };
+        if written != size {
+            return None;
+        }
+        let path =
+            unsafe { CStr::from_ptr(info.pvi_cdir.vip_path.as_ptr() as *const libc::c_char) };
+        let path = path.to_string_lossy().into_owned();
+        (!path.is_empty()).then_some(path)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod sys {
+    use std::fs;
+
+    fn proc_path(pid: u32, entry: &str) -> String {
+        format!("/proc/{}/{}", pid, entry)
+    }
+
+    /// Parent PID, effective UID and start time from `/proc/<pid>/stat` and
+    /// `/proc/<pid>/status`.
+    pub(super) fn basic_info(pid: u32) -> Option<(u32, Option<u32>, u64)> {
+        let stat = fs::read_to_string(proc_path(pid, "stat")).ok()?;
+        // Fields after the parenthesised command name, starting at `state`
+        let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let ppid = fields.get(1)?.parse().ok()?;
+        let start_time = fields.get(19)?.parse().ok()?;
+
+        let uid = fs::read_to_string(proc_path(pid, "status"))
+            .ok()
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Uid:"))
+                    .and_then(|ids| ids.split_whitespace().nth(1))
+                    .and_then(|uid| uid.parse().ok())
+            });
+        Some((ppid, uid, start_time))
+    }
+
+    pub(super) fn executable(pid: u32) -> Option<String> {
+        let path = fs::read_link(proc_path(pid, "exe")).ok()?;
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    /// Splits a NUL-separated `/proc` file such as `cmdline` or `environ`.
+    fn read_nul_separated(pid: u32, entry: &str) -> Option<Vec<String>> {
+        let contents = fs::read(proc_path(pid, entry)).ok()?;
+        Some(
+            contents
+                .split(|&b| b == 0)
+                .filter(|item| !item.is_empty())
+                .map(|item| String::from_utf8_lossy(item).into_owned())
+                .collect(),
+        )
+    }
+
+    pub(super) fn arguments(pid: u32) -> Option<Vec<String>> {
+        read_nul_separated(pid, "cmdline")
+    }
+
+    pub(super) fn environment(pid: u32) -> Option<Vec<String>> {
+        read_nul_separated(pid, "environ")
+    }
+
+    pub(super) fn working_directory(pid: u32) -> Option<String> {
+        let path = fs::read_link(proc_path(pid, "cwd")).ok()?;
+        Some(path.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_procargs2() {
+        let mut buffer = 2i32.to_ne_bytes().to_vec();
+        buffer.extend_from_slice(b"/usr/bin/vim\0\0\0\0vim\0src/main.rs\0HOME=/Users/me\0");
+        buffer.extend_from_slice(b"\0\0ptr_munge=\0");
+        let (argv, environment) = parse_procargs2(&buffer).unwrap();
+        assert_eq!(argv, vec!["vim", "src/main.rs"]);
+        assert_eq!(environment, vec!["HOME=/Users/me"]);
+        assert_eq!(parse_procargs2(&buffer[..2]), None);
+    }
+
+    #[test]
+    fn test_lookup_current_process() {
+        let pid = std::process::id();
+        let mut cache = ProcessInfoCache::new();
+        let info = cache.get(pid).cloned().expect("own process is visible");
+        assert_eq!(info.pid, pid);
+        assert!(info.exe.is_some());
+        assert!(!info.argv.is_empty());
+        assert_eq!(
+            info.cwd.as_ref().map(std::path::PathBuf::from),
+            std::env::current_dir().ok()
+        );
+        assert_eq!(cache.get(pid), Some(&info));
+
+        // cargo runs tests with its package variables set
+        assert!(cache
+            .environment(pid)
+            .unwrap()
+            .iter()
+            .any(|entry| entry.starts_with("CARGO_PKG_NAME=")));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::process_info::parent_pid;
+use crate::process_watch::{parent_table, read_processes};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, error};
+
+/// How long a member that left the process table keeps matching, so events
+/// fs_usage delivers after a short-lived child exited are still reported.
+const EXIT_GRACE: Duration = Duration::from_secs(5);
+
+/// Maximum number of ancestors looked up when classifying an unknown PID.
+const MAX_ANCESTRY_DEPTH: usize = 32;
+
+/// Tracks a set of root PIDs and every process descended from them.
+///
+/// Membership is derived from process table snapshots, which the monitor's
+/// process poller takes periodically and passes to `update`. An unknown PID is classified by
+/// `classify`, which follows its parents through `parent_pid` without
+/// forking, catching children that fork, write and exit between two scans.
+/// `contains` only consults what is already known. Members that exited keep
+/// matching for `EXIT_GRACE` before being dropped.
+pub(crate) struct ProcessTree {
+    state: Mutex<TreeState>,
+}
+
+#[derive(Default)]
+struct TreeState {
+    roots: Vec<u32>,
+    members: HashSet<u32>,
+    /// Members missing from the process table, with when they were noticed.
+    exited: HashMap<u32, Instant>,
+    others: HashSet<u32>,
+}
+
+impl TreeState {
+    fn is_member(&self, pid: u32) -> bool {
+        self.members.contains(&pid) || self.exited.contains_key(&pid)
+    }
+
+    /// Rebuilds membership from a process table snapshot taken at `now`.
+    /// Live members stay members even if reparented after their parent
+    /// exited.
+    fn apply(&mut self, table: &[(u32, u32)], now: Instant) {
+        let alive: HashSet<u32> = table.iter().map(|(pid, _)| *pid).collect();
+        let seeds: Vec<u32> = self
+            .roots
+            .iter()
+            .copied()
+            .chain(
+                self.members
+                    .iter()
+                    .copied()
+                    .filter(|pid| alive.contains(pid)),
+            )
+            .collect();
+        let mut members = descendants(&seeds, table);
+        // Roots that have already exited are still tracked
+        members.extend(self.roots.iter().copied());
+
+        for pid in self.members.difference(&members) {
+            self.exited.entry(*pid).or_insert(now);
+        }
+        self.exited
+            .retain(|pid, since| !members.contains(pid) && now.duration_since(*since) < EXIT_GRACE);
+        self.others = alive.difference(&members).copied().collect();
+        self.members = members;
+    }
+}
+
+impl ProcessTree {
+    pub(crate) fn new(roots: Vec<u32>) -> Self {
+        let members = roots.iter().copied().collect();
+        Self {
+            state: Mutex::new(TreeState {
+                roots,
+                members,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Whether `pid` is a known member. PIDs not seen since the last
+    /// refresh match only once `classify` has placed them.
+    pub(crate) fn contains(&self, pid: u32) -> bool {
+        self.state.lock().unwrap().is_member(pid)
+    }
+
+    /// Places `pid` in or out of the tree by looking up its ancestry, unless
+    /// it is already known. Called by the reader thread before taking the
+    /// filter lock, so the lookups never run under it.
+    pub(crate) fn classify(&self, pid: u32) {
+        {
+            let state = self.state.lock().unwrap();
+            if state.is_member(pid) || state.others.contains(&pid) {
+                return;
+            }
+        }
+        self.adopt(pid, parent_pid);
+    }
+
+    /// Adds `pid` and its intermediate ancestors if one of its ancestors is a
+    /// member, following `parent_of` up the process hierarchy. A lineage
+    /// that ends outside the tree is remembered as such; one that cannot be
+    /// followed, because a process already exited, is left unclassified.
+    fn adopt(&self, pid: u32, parent_of: impl Fn(u32) -> Option<u32>) -> bool {
+        let mut lineage = vec![pid];
+        let mut current = pid;
+        for _ in 0..MAX_ANCESTRY_DEPTH {
+            let Some(parent) = parent_of(current) else {
+                return false;
+            };
+            let mut state = self.state.lock().unwrap();
+            if parent == 0 || parent == current || state.others.contains(&parent) {
+                state.others.extend(lineage);
+                return false;
+            }
+            if state.is_member(parent) {
+                debug!("Adopted PID {} into the process tree", pid);
+                for pid in lineage {
+                    state.others.remove(&pid);
+                    state.members.insert(pid);
+                }
+                return true;
+            }
+            drop(state);
+            lineage.push(parent);
+            current = parent;
+        }
+        false
+    }
+
+    pub(crate) fn refresh(&self) {
+        let table = match read_processes() {
+            Ok(processes) => parent_table(&processes),
+            Err(e) => {
+                error!("Failed to read process table: {}", e);
+                return;
+            }
+        };
+
+        self.update(&table);
+    }
+
+    /// Rebuilds membership from a `(pid, ppid)` process table snapshot.
+    pub(crate) fn update(&self, table: &[(u32, u32)]) {
+        let mut state = self.state.lock().unwrap();
+        state.apply(table, Instant::now());
+        debug!("Process tree now tracks {} PIDs", state.members.len());
+    }
+
+    pub(crate) fn add_root(&self, pid: u32) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.roots.push(pid);
+            state.members.insert(pid);
+            state.others.remove(&pid);
+        }
+        self.refresh();
+    }
+
+    pub(crate) fn pids(&self) -> Vec<u32> {
+        let mut pids: Vec<u32> = self.state.lock().unwrap().members.iter().copied().collect();
+        pids.sort_unstable();
+        pids
+    }
+}
+
+/// Returns the roots plus every PID reachable from them through parent links.
+fn descendants(roots: &[u32], table: &[(u32, u32)]) -> HashSet<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, ppid) in table {
+        if pid != ppid {
+            children.entry(*ppid).or_default().push(*pid);
+        }
+    }
+
+    let mut members: HashSet<u32> = roots.iter().copied().collect();
+    let mut queue: Vec<u32> = roots.to_vec();
+    while let Some(pid) = queue.pop() {
+        for child in children.get(&pid).into_iter().flatten() {
+            if members.insert(*child) {
+                queue.push(*child);
+            }
+        }
+    }
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lineage() {
+        let mut state = TreeState {
+            roots: vec![100],
+            ..Default::default()
+        };
+        let started = Instant::now();
+        state.apply(
+            &[(1, 0), (100, 1), (101, 100), (102, 101), (200, 1)],
+            started,
+        );
+        assert!(state.is_member(101) && state.is_member(102));
+        assert!(!state.is_member(200) && state.others.contains(&200));
+
+        // 101 exits and 102 is reparented to launchd: 102 stays a member and
+        // 101 keeps matching for the grace period
+        let table = [(1, 0), (100, 1), (102, 1), (200, 1)];
+        state.apply(&table, started + Duration::from_secs(1));
+        assert!(state.is_member(101) && state.is_member(102));
+        state.apply(&table, started + Duration::from_secs(1) + EXIT_GRACE);
+        assert!(!state.is_member(101) && state.is_member(102));
+    }
+
+    #[test]
+    fn test_adopt_short_lived_child() {
+        let tree = ProcessTree::new(vec![100]);
+        tree.state.lock().unwrap().others.insert(1);
+        // 300 was forked by 250, a child of the root, after the last scan
+        let parents = HashMap::from([(300, 250), (250, 100), (400, 1)]);
+        let parent_of = |pid| parents.get(&pid).copied();
+
+        assert!(tree.adopt(300, parent_of));
+        assert_eq!(tree.pids(), vec![100, 250, 300]);
+        assert!(tree.contains(300));
+
+        // 400 descends from launchd and is remembered as outside the tree;
+        // 500 has already exited and stays unclassified
+        assert!(!tree.adopt(400, parent_of));
+        assert!(tree.state.lock().unwrap().others.contains(&400));
+        assert!(!tree.adopt(500, parent_of));
+        assert!(!tree.state.lock().unwrap().others.contains(&500));
+    }
+
+    #[test]
+    fn test_descendants() {
+        let table = [
+            (1, 0),
+            (100, 1),
+            (101, 100),
+            (102, 101),
+            (103, 100),
+            (200, 1),
+            (201, 200),
+        ];
+
+        let members = descendants(&[100], &table);
+        let mut pids: Vec<u32> = members.into_iter().collect();
+        pids.sort_unstable();
+        assert_eq!(pids, vec![100, 101, 102, 103]);
+
+        // Roots that have already exited are still tracked
+        let members = descendants(&[999], &table);
+        assert_eq!(members.into_iter().collect::<Vec<_>>(), vec![999]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::MonitorEvent;
+use std::collections::HashMap;
+use std::process::Command;
+
+/// A row of the process table.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProcessEntry {
+    pub(crate) ppid: u32,
+    /// `ps` state letters, e.g. `S`, `R+` or `Z` for a zombie.
+    pub(crate) state: String,
+    /// Start time as printed by `ps -o lstart`, which tells a reused PID
+    /// apart from the process that held it before.
+    pub(crate) started: String,
+    pub(crate) argv: Vec<String>,
+}
+
+impl ProcessEntry {
+    pub(crate) fn name(&self) -> String {
+        self.argv
+            .first()
+            .map(|arg0| arg0.rsplit('/').next().unwrap_or(arg0).to_string())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn is_zombie(&self) -> bool {
+        self.state.starts_with('Z')
+    }
+}
+
+/// Diffs successive process table snapshots into `ProcessStarted` and
+/// `ProcessExited` events. A process exits when its PID disappears; a PID
+/// whose start time changed was reused and is reported as an exit followed by
+/// a start. Reparenting and exec keep the start time and report nothing.
+pub(crate) struct ProcessWatcher {
+    known: HashMap<u32, ProcessEntry>,
+}
+
+impl ProcessWatcher {
+    pub(crate) fn new(initial: HashMap<u32, ProcessEntry>) -> Self {
+        Self { known: initial }
+    }
+
+    pub(crate) fn update(&mut self, current: HashMap<u32, ProcessEntry>) -> Vec<MonitorEvent> {
+        let same_process = |before: Option<&ProcessEntry>, after: Option<&ProcessEntry>| matches!((before, after), (Some(before), Some(after)) if before.started == after.started);
+        let mut exited: Vec<u32> = self
+            .known
+            .iter()
+            .filter(|(pid, entry)| !same_process(Some(entry), current.get(pid)))
+            .map(|(pid, _)| *pid)
+            .collect();
+        let mut started: Vec<(&u32, &ProcessEntry)> = current
+            .iter()
+            .filter(|(pid, entry)| !same_process(self.known.get(pid), Some(entry)))
+            .collect();
+        exited.sort_unstable();
+        started.sort_by_key(|(pid, _)| **pid);
+
+        let mut events: Vec<MonitorEvent> = exited
+            .into_iter()
+            .map(|pid| MonitorEvent::ProcessExited { pid })
+            .collect();
+        events.extend(
+            started
+                .into_iter()
+                .map(|(pid, entry)| MonitorEvent::ProcessStarted {
+                    pid: *pid,
+                    ppid: entry.ppid,
+                    name: entry.name(),
+                    argv: entry.argv.clone(),
+                }),
+        );
+
+        self.known = current;
+        events
+    }
+}
+
+pub(crate) fn read_processes() -> std::io::Result<HashMap<u32, ProcessEntry>> {
+    let output = Command::new("ps")
+        .args(["-axww", "-o", "pid=,ppid=,state=,lstart=,args="])
+        .output()?;
+    Ok(parse_ps_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// `(pid, ppid)` pairs of a snapshot, as `ProcessTree::update` takes them.
+pub(crate) fn parent_table(processes: &HashMap<u32, ProcessEntry>) -> Vec<(u32, u32)> {
+    processes
+        .iter()
+        .map(|(pid, entry)| (*pid, entry.ppid))
+        .collect()
+}
+
+/// Parses `ps -o pid=,ppid=,state=,lstart=,args=` output. `lstart` is always five
+/// words, e.g. `Sat Oct 18 09:12:01 2026`. `args` is split on whitespace, so
+/// arguments containing spaces are split too.
+fn parse_ps_output(output: &str) -> HashMap<u32, ProcessEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let state = fields.next()?.to_string();
+            let started: Vec<&str> = fields.by_ref().take(5).collect();
+            if started.len() < 5 {
+                return None;
+            }
+            let argv = fields.map(str::to_string).collect();
+            Some((
+                pid,
+                ProcessEntry {
+                    ppid,
+                    state,
+                    started: started.join(" "),
+                    argv,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOT: &str = "Sat Oct 18 09:00:00 2026";
+    const EARLIER: &str = "Sat Oct 18 10:00:00 2026";
+    const LATER: &str = "Sat Oct 18 10:05:00 2026";
+
+    fn ps(rows: &[(u32, u32, &str, &str)]) -> HashMap<u32, ProcessEntry> {
+        let output: String = rows
+            .iter()
+            .map(|(pid, ppid, started, args)| format!("{pid:5} {ppid:5} Ss {started} {args}\n"))
+            .collect();
+        parse_ps_output(&output)
+    }
+
+    #[test]
+    fn test_process_events() {
+        let before = ps(&[
+            (1, 0, BOOT, "/sbin/launchd"),
+            (100, 1, EARLIER, "/bin/zsh -l"),
+            (200, 100, EARLIER, "vim a.rs"),
+        ]);
+        let after = ps(&[
+            (1, 0, BOOT, "/sbin/launchd"),
+            (100, 1, EARLIER, "/usr/bin/login -fp"),
+            (300, 100, LATER, "/usr/bin/make test"),
+            (200, 100, LATER, "cargo build"),
+        ]);
+
+        let mut watcher = ProcessWatcher::new(before);
+        let events = watcher.update(after.clone());
+        assert_eq!(
+            events,
+            vec![
+                // 200 was reused by a different process; 100 only exec'd
+                MonitorEvent::ProcessExited { pid: 200 },
+                MonitorEvent::ProcessStarted {
+                    pid: 200,
+                    ppid: 100,
+                    name: "cargo".to_string(),
+                    argv: vec!["cargo".to_string(), "build".to_string()],
+                },
+                MonitorEvent::ProcessStarted {
+                    pid: 300,
+                    ppid: 100,
+                    name: "make".to_string(),
+                    argv: vec!["/usr/bin/make".to_string(), "test".to_string()],
+                },
+            ]
+        );
+
+        // 300 is reparented to launchd when its parent goes away
+        let reparented = ps(&[
+            (1, 0, BOOT, "/sbin/launchd"),
+            (300, 1, LATER, "/usr/bin/make test"),
+        ]);
+        assert_eq!(
+            watcher.update(reparented),
+            vec![
+                MonitorEvent::ProcessExited { pid: 100 },
+                MonitorEvent::ProcessExited { pid: 200 },
+            ]
+        );
+        assert_eq!(
+            watcher.update(ps(&[(1, 0, BOOT, "/sbin/launchd")])),
+            vec![MonitorEvent::ProcessExited { pid: 300 }]
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use crate::error::{FsUsageError, Result};
+use crate::{FsEvent, MonitorEvent};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Token bucket parameters: a sustained rate plus a burst allowance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub events_per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(events_per_second: f64, burst: u32) -> Self {
+        Self {
+            events_per_second,
+            burst,
+        }
+    }
+}
+
+/// Rate limiting and sampling applied on the reader thread after filtering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    /// Token bucket per PID.
+    pub per_process: Option<RateLimit>,
+    /// Token bucket per path.
+    pub per_path: Option<RateLimit>,
+    /// Keep one in every `sample_every` events per process. `1` keeps all.
+    pub sample_every: u32,
+    /// How often `MonitorEvent::Suppressed` summaries are emitted, and how
+    /// long an idle process keeps its sampling position.
+    pub summary_interval: Duration,
+}
+
+impl RateLimitConfig {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.per_process.is_some() || self.per_path.is_some() || self.sample_every > 1
+    }
+
+    /// Rejects limits that would never let an event through: a rate that is
+    /// not a positive finite number, or a burst of zero.
+    pub(crate) fn validate(&self) -> Result<()> {
+        for limit in self.per_process.iter().chain(&self.per_path) {
+            if !(limit.events_per_second.is_finite() && limit.events_per_second > 0.0) {
+                return Err(FsUsageError::InvalidState(format!(
+                    "rate limit must be a positive number of events per second, got {}",
+                    limit.events_per_second
+                )));
+            }
+            if limit.burst == 0 {
+                return Err(FsUsageError::InvalidState(
+                    "rate limit burst must be at least 1".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_process: None,
+            per_path: None,
+            sample_every: 1,
+            summary_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.events_per_second).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    fn try_take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_full(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        self.refill(limit, now);
+        self.tokens >= limit.burst as f64
+    }
+}
+
+struct SampleCounter {
+    count: u32,
+    seen: Instant,
+}
+
+/// State for `RateLimitConfig`, shared by the reader thread and the summary
+/// timer.
+pub(crate) struct RateLimiter {
+    process_buckets: HashMap<u32, Bucket>,
+    path_buckets: HashMap<String, Bucket>,
+    sample_counters: HashMap<u32, SampleCounter>,
+    suppressed: HashMap<u32, (String, u64)>,
+    last_summary: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            process_buckets: HashMap::new(),
+            path_buckets: HashMap::new(),
+            sample_counters: HashMap::new(),
+            suppressed: HashMap::new(),
+            last_summary: now,
+        }
+    }
+
+    /// Returns whether `event` may be sent, counting it as suppressed otherwise.
+    pub(crate) fn allow(
+        &mut self,
+        event: &FsEvent,
+        config: &RateLimitConfig,
+        now: Instant,
+    ) -> bool {
+        let allowed = self.sample(event, config, now)
+            && config.per_process.as_ref().is_none_or(|limit| {
+                self.process_buckets
+                    .entry(event.pid)
+                    .or_insert_with(|| Bucket::new(limit, now))
+                    .try_take(limit, now)
+            })
+            && config.per_path.as_ref().is_none_or(|limit| {
+                self.path_buckets
+                    .entry(event.path.clone())
+                    .or_insert_with(|| Bucket::new(limit, now))
+                    .try_take(limit, now)
+            });
+
+        if !allowed {
+            let entry = self
+                .suppressed
+                .entry(event.pid)
+                .or_insert_with(|| (event.process_name.clone(), 0));
+            entry.1 += 1;
+        }
+        allowed
+    }
+
+    fn sample(&mut self, event: &FsEvent, config: &RateLimitConfig, now: Instant) -> bool {
+        if config.sample_every <= 1 {
+            return true;
+        }
+        let counter = self
+            .sample_counters
+            .entry(event.pid)
+            .or_insert(SampleCounter {
+                count: 0,
+                seen: now,
+            });
+        let keep = counter.count == 0;
+        counter.count = (counter.count + 1) % config.sample_every;
+        counter.seen = now;
+        keep
+    }
+
+    /// Emits one summary per process with suppressed events once
+    /// `summary_interval` has elapsed, and forgets idle buckets and sample
+    /// counters. Called from a timer, so drops are reported even if no
+    /// further events arrive.
+    pub(crate) fn summaries(
+        &mut self,
+        config: &RateLimitConfig,
+        now: Instant,
+    ) -> Vec<MonitorEvent> {
+        if now.saturating_duration_since(self.last_summary) < config.summary_interval {
+            return Vec::new();
+        }
+        self.last_summary = now;
+
+        if let Some(limit) = &config.per_process {
+            self.process_buckets
+                .retain(|_, bucket| !bucket.is_full(limit, now));
+        }
+        if let Some(limit) = &config.per_path {
+            self.path_buckets
+                .retain(|_, bucket| !bucket.is_full(limit, now));
+        }
+        self.sample_counters.retain(|_, counter| {
+            now.saturating_duration_since(counter.seen) < config.summary_interval
+        });
+
+        let mut suppressed: Vec<_> = self.suppressed.drain().collect();
+        suppressed.sort_by_key(|(pid, _)| *pid);
+        suppressed
+            .into_iter()
+            .map(|(pid, (process_name, count))| MonitorEvent::Suppressed {
+                pid,
+                process_name,
+                count,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(pid: u32, path: &str) -> FsEvent {
+        FsEvent {
+            process_name: format!("proc{}", pid),
+            pid,
+            operation: "write".to_string(),
+            path: path.to_string(),
+            result: "OK".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_per_process_token_bucket() {
+        let start = Instant::now();
+        let config = RateLimitConfig {
+            per_process: Some(RateLimit::new(10.0, 3)),
+            ..Default::default()
+        };
+        let mut limiter = RateLimiter::new(start);
+
+        let allowed = (0..5)
+            .filter(|_| limiter.allow(&event(1, "/a"), &config, start))
+            .count();
+        assert_eq!(allowed, 3);
+
+        // A different process has its own bucket
+        assert!(limiter.allow(&event(2, "/a"), &config, start));
+
+        // 100ms at 10/s refills one token
+        let later = start + Duration::from_millis(100);
+        assert!(limiter.allow(&event(1, "/a"), &config, later));
+        assert!(!limiter.allow(&event(1, "/a"), &config, later));
+
+        assert!(limiter.summaries(&config, later).is_empty());
+        let summaries = limiter.summaries(&config, start + Duration::from_secs(1));
+        assert_eq!(
+            summaries,
+            vec![MonitorEvent::Suppressed {
+                pid: 1,
+                process_name: "proc1".to_string(),
+                count: 3,
+            }]
+        );
+        assert!(limiter
+            .summaries(&config, start + Duration::from_secs(2))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_per_path_and_sampling() {
+        let start = Instant::now();
+        let config = RateLimitConfig {
+            per_path: Some(RateLimit::new(1.0, 1)),
+            ..Default::default()
+        };
+        let mut limiter = RateLimiter::new(start);
+        assert!(limiter.allow(&event(1, "/a"), &config, start));
+        assert!(!limiter.allow(&event(2, "/a"), &config, start));
+        assert!(limiter.allow(&event(2, "/b"), &config, start));
+
+        let config = RateLimitConfig {
+            sample_every: 3,
+            ..Default::default()
+        };
+        let mut limiter = RateLimiter::new(start);
+        let kept: Vec<bool> = (0..6)
+            .map(|_| limiter.allow(&event(1, "/a"), &config, start))
+            .collect();
+        assert_eq!(kept, vec![true, false, false, true, false, false]);
+
+        // Idle processes are forgotten; the summary reports their drops
+        let summaries = limiter.summaries(&config, start + Duration::from_secs(2));
+        assert_eq!(summaries.len(), 1);
+        assert!(limiter.sample_counters.is_empty());
+        assert!(limiter.allow(&event(1, "/a"), &config, start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |events_per_second, burst| RateLimitConfig {
+            per_path: Some(RateLimit::new(events_per_second, burst)),
+            ..Default::default()
+        };
+        assert!(config(0.5, 1).validate().is_ok());
+        for invalid in [
+            config(0.0, 5),
+            config(-1.0, 5),
+            config(f64::NAN, 5),
+            config(10.0, 0),
+        ] {
+            assert!(matches!(
+                invalid.validate(),
+                Err(FsUsageError::InvalidState(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::classifier::{is_open_operation, OperationClassifier};
+use crate::{FsEvent, OpenFlags, OperationType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// Upper bound on open sessions. Descriptors inherited from before the
+/// monitor started are never opened or closed in the trace, so without a cap
+/// long-running processes would grow the table forever.
+const MAX_OPEN_SESSIONS: usize = 65_536;
+
+const MICROS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000;
+
+/// Why a `FileSession` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionEnd {
+    /// The descriptor was closed.
+    Closed,
+    /// The process exited with the descriptor still open.
+    ProcessExited,
+    /// The descriptor number was returned by a new open without a close
+    /// being seen.
+    Reopened,
+}
+
+/// Activity on one file descriptor between its open and close, produced by
+/// `FsUsageMonitor::sessions()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileSession {
+    pub pid: u32,
+    pub process_name: String,
+    pub fd: u32,
+    pub path: String,
+    pub open_flags: Option<OpenFlags>,
+    pub opened_at: String,
+    pub closed_at: String,
+    /// Time between the open and the last event, from fs_usage timestamps.
+    pub duration: Duration,
+    /// Number of calls per fs_usage operation, excluding the open and close.
+    pub operations: BTreeMap<String, u64>,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// The last error reported on the descriptor, including by the close.
+    pub error: Option<String>,
+    pub end: SessionEnd,
+}
+
+impl FileSession {
+    fn open(event: &FsEvent, fd: u32) -> Self {
+        Self {
+            pid: event.pid,
+            process_name: event.process_name.clone(),
+            fd,
+            path: event.path.clone(),
+            open_flags: event.open_flags,
+            opened_at: event.timestamp.clone(),
+            closed_at: event.timestamp.clone(),
+            duration: Duration::ZERO,
+            operations: BTreeMap::new(),
+            bytes_read: 0,
+            bytes_written: 0,
+            error: None,
+            end: SessionEnd::Closed,
+        }
+    }
+
+    fn record(&mut self, event: &FsEvent, classifier: &OperationClassifier) {
+        *self.operations.entry(event.operation.clone()).or_default() += 1;
+        let bytes = event.bytes.unwrap_or(0);
+        if classifier.matches(&OperationType::Read, &event.operation) {
+            self.bytes_read += bytes;
+        } else if classifier.matches(&OperationType::Write, &event.operation) {
+            self.bytes_written += bytes;
+        }
+        self.touch(event);
+    }
+
+    fn touch(&mut self, event: &FsEvent) {
+        if event.result != "OK" {
+            self.error = Some(event.result.clone());
+        }
+        self.closed_at = event.timestamp.clone();
+    }
+
+    fn finish(mut self, end: SessionEnd) -> Self {
+        self.end = end;
+        self.duration = match (
+            timestamp_micros(&self.opened_at),
+            timestamp_micros(&self.closed_at),
+        ) {
+            // Timestamps are time of day, so allow for crossing midnight
+            (Some(start), Some(end)) => {
+                Duration::from_micros((end + MICROS_PER_DAY - start) % MICROS_PER_DAY)
+            }
+            _ => Duration::ZERO,
+        };
+        self
+    }
+}
+
+/// Groups per-(pid, fd) activity into `FileSession`s. Fed raw events on the
+/// reader thread, since closes and most descriptor I/O carry no path.
+#[derive(Default)]
+pub(crate) struct SessionTracker {
+    sessions: HashMap<(u32, u32), FileSession>,
+}
+
+impl SessionTracker {
+    /// Updates sessions from `event` and returns any that ended. Opens only
+    /// start a session when `track` is set, i.e. the open passed the
+    /// monitor's filters.
+    pub(crate) fn observe(
+        &mut self,
+        event: &FsEvent,
+        track: bool,
+        classifier: &OperationClassifier,
+    ) -> Vec<FileSession> {
+        if event.operation == "exit" {
+            return self.process_exited(event.pid);
+        }
+
+        let Some(fd) = event.fd else {
+            return Vec::new();
+        };
+        let key = (event.pid, fd);
+
+        if is_open_operation(&event.operation) {
+            if event.result != "OK" {
+                return Vec::new();
+            }
+            let ended = self
+                .sessions
+                .remove(&key)
+                .map(|session| session.finish(SessionEnd::Reopened));
+            if track {
+                if self.sessions.len() >= MAX_OPEN_SESSIONS {
+                    self.sessions.clear();
+                }
+                self.sessions.insert(key, FileSession::open(event, fd));
+            }
+            return ended.into_iter().collect();
+        }
+
+        if matches!(event.operation.as_str(), "close" | "close_nocancel") {
+            return self
+                .sessions
+                .remove(&key)
+                .map(|mut session| {
+                    session.touch(event);
+                    session.finish(SessionEnd::Closed)
+                })
+                .into_iter()
+                .collect();
+        }
+
+        if let Some(session) = self.sessions.get_mut(&key) {
+            session.record(event, classifier);
+        }
+        Vec::new()
+    }
+
+    /// Ends every session of `pid`.
+    pub(crate) fn process_exited(&mut self, pid: u32) -> Vec<FileSession> {
+        let mut fds: Vec<u32> = self
+            .sessions
+            .keys()
+            .filter(|(session_pid, _)| *session_pid == pid)
+            .map(|(_, fd)| *fd)
+            .collect();
+        fds.sort_unstable();
+        fds.into_iter()
+            .filter_map(|fd| self.sessions.remove(&(pid, fd)))
+            .map(|session| session.finish(SessionEnd::ProcessExited))
+            .collect()
+    }
+}
+
+/// Microseconds since midnight for an fs_usage `HH:MM:SS.ffffff` timestamp.
+fn timestamp_micros(timestamp: &str) -> Option<u64> {
+    let (clock, fraction) = timestamp.split_once('.').unwrap_or((timestamp, "0"));
+    let mut fields = clock.split(':').map(|field| field.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (fields.next()??, fields.next()??, fields.next()??);
+    let micros: u64 = format!("{:0<6}", fraction).get(..6)?.parse().ok()?;
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1_000_000 + micros)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(timestamp: &str, operation: &str, fd: Option<u32>, bytes: Option<u64>) -> FsEvent {
+        FsEvent {
+            timestamp: timestamp.to_string(),
+            process_name: "cargo".to_string(),
+            pid: 9,
+            operation: operation.to_string(),
+            result: "OK".to_string(),
+            fd,
+            bytes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let classifier = OperationClassifier::default();
+        let mut tracker = SessionTracker::default();
+
+        let open = FsEvent {
+            path: "/repo/target/out.rlib".to_string(),
+            open_flags: OpenFlags::parse("(_WC_T_)"),
+            ..event("10:00:00.000000", "open", Some(4), None)
+        };
+        assert!(tracker.observe(&open, true, &classifier).is_empty());
+        for (timestamp, bytes) in [("10:00:00.010000", 0x1000), ("10:00:00.030000", 0x800)] {
+            tracker.observe(
+                &event(timestamp, "write", Some(4), Some(bytes)),
+                false,
+                &classifier,
+            );
+        }
+        tracker.observe(
+            &event("10:00:00.035000", "fsync", Some(4), None),
+            false,
+            &classifier,
+        );
+        // Other descriptors are ignored
+        tracker.observe(
+            &event("10:00:00.036000", "read", Some(5), Some(10)),
+            false,
+            &classifier,
+        );
+
+        let mut close = event("10:00:00.040000", "close", Some(4), None);
+        close.result = "5".to_string();
+        let ended = tracker.observe(&close, false, &classifier);
+        assert_eq!(ended.len(), 1);
+        let session = &ended[0];
+        assert_eq!(session.path, "/repo/target/out.rlib");
+        assert_eq!(session.operations.get("write"), Some(&2));
+        assert_eq!(session.operations.get("fsync"), Some(&1));
+        assert_eq!(session.bytes_written, 0x1800);
+        assert_eq!(session.bytes_read, 0);
+        assert_eq!(session.duration, Duration::from_millis(40));
+        assert_eq!(session.error.as_deref(), Some("5"));
+        assert_eq!(session.end, SessionEnd::Closed);
+    }
+
+    #[test]
+    fn test_untracked_reopened_and_exited() {
+        let classifier = OperationClassifier::default();
+        let mut tracker = SessionTracker::default();
+        let open = |fd: u32, path: &str| FsEvent {
+            path: path.to_string(),
+            ..event("23:59:59.900000", "open", Some(fd), None)
+        };
+
+        // Opens filtered out by the monitor are not tracked
+        tracker.observe(&open(3, "/elsewhere"), false, &classifier);
+        assert!(tracker
+            .observe(
+                &event("23:59:59.950000", "close", Some(3), None),
+                false,
+                &classifier
+            )
+            .is_empty());
+
+        tracker.observe(&open(3, "/a"), true, &classifier);
+        let ended = tracker.observe(&open(3, "/b"), true, &classifier);
+        assert_eq!(ended[0].path, "/a");
+        assert_eq!(ended[0].end, SessionEnd::Reopened);
+
+        tracker.observe(&open(6, "/c"), true, &classifier);
+        tracker.observe(
+            &event("00:00:00.100000", "read", Some(6), Some(3)),
+            false,
+            &classifier,
+        );
+        let ended = tracker.observe(
+            &event("00:00:00.100000", "exit", None, None),
+            false,
+            &classifier,
+        );
+        let paths: Vec<_> = ended.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, vec!["/b", "/c"]);
+        assert!(ended.iter().all(|s| s.end == SessionEnd::ProcessExited));
+        // Crossing midnight
+        assert_eq!(ended[1].duration, Duration::from_millis(200));
+        assert_eq!(ended[1].bytes_read, 3);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::backpressure::{BackpressurePolicy, Disconnected, PolicySender};
+use crate::FsEvent;
+use crossbeam_channel::{Receiver, TryRecvError};
+use futures_core::Stream;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+/// Async view of a monitor's events, returned by `FsUsageMonitor::stream()`.
+///
+/// fs_usage is still read on the monitor's reader thread rather than through
+/// `tokio::process`, so one fs_usage process can feed every consumer and the
+/// stream works on any executor. The reader thread delivers into the stream's
+/// own channel, bounded by `channel_capacity` and governed by the monitor's
+/// `BackpressurePolicy` like `events()`, and wakes the task waiting on it.
+/// The stream ends when the monitor is stopped or dropped.
+pub struct EventStream {
+    receiver: Receiver<FsEvent>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl EventStream {
+    /// Waits for the next event. Returns `None` once the monitor is stopped
+    /// or dropped.
+    pub async fn recv(&mut self) -> Option<FsEvent> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    fn try_next(&self) -> Poll<Option<FsEvent>> {
+        match self.receiver.try_recv() {
+            Ok(event) => Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = FsEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FsEvent>> {
+        if let Poll::Ready(next) = self.try_next() {
+            return Poll::Ready(next);
+        }
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        // Checked again in case an event arrived before the waker was stored
+        self.try_next()
+    }
+}
+
+/// The reader thread's side of an `EventStream`.
+pub(crate) struct StreamSender {
+    sender: PolicySender<FsEvent>,
+    waker: Weak<Mutex<Option<Waker>>>,
+}
+
+impl StreamSender {
+    /// Whether the `EventStream` has been dropped.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.waker.strong_count() == 0
+    }
+
+    /// Sends `event` under the backpressure policy. A blocked send gives up
+    /// once `keep_waiting` returns false or the stream is dropped.
+    pub(crate) fn send(
+        &mut self,
+        event: FsEvent,
+        keep_waiting: &dyn Fn() -> bool,
+    ) -> Result<(), Disconnected> {
+        let waker = &self.waker;
+        let sent = self
+            .sender
+            .send(event, || keep_waiting() && waker.strong_count() > 0);
+        self.wake();
+        sent
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), Disconnected> {
+        let flushed = self.sender.flush();
+        self.wake();
+        flushed
+    }
+
+    fn wake(&self) {
+        let waker = self
+            .waker
+            .upgrade()
+            .and_then(|waker| waker.lock().unwrap().take());
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for StreamSender {
+    fn drop(&mut self) {
+        // Lets a waiting stream observe the end
+        self.wake();
+    }
+}
+
+/// Creates a stream whose channel holds up to `capacity` events (unbounded
+/// for `None`), applying `policy` when full.
+pub(crate) fn channel(
+    capacity: Option<usize>,
+    policy: BackpressurePolicy,
+    dropped: Arc<AtomicU64>,
+) -> (StreamSender, EventStream) {
+    let (sender, receiver) = match capacity {
+        Some(capacity) => crossbeam_channel::bounded(capacity),
+        None => crossbeam_channel::unbounded(),
+    };
+    let waker = Arc::new(Mutex::new(None));
+    let stream_sender = StreamSender {
+        sender: PolicySender::new(sender, receiver.clone(), policy, dropped),
+        waker: Arc::downgrade(&waker),
+    };
+    (stream_sender, EventStream { receiver, waker })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+    use std::sync::atomic::Ordering;
+
+    fn event(path: &str) -> FsEvent {
+        FsEvent {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (mut sender, mut stream) = channel(None, BackpressurePolicy::Block, dropped);
+
+        // Sent from a plain thread, as the reader thread does
+        std::thread::spawn(move || {
+            for path in ["/a", "/b"] {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                sender.send(event(path), &|| true).unwrap();
+            }
+        });
+
+        assert_eq!(stream.recv().await.unwrap().path, "/a");
+        let next = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        assert_eq!(next.unwrap().path, "/b");
+        assert!(stream.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_applies_policy() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (mut sender, mut stream) =
+            channel(Some(2), BackpressurePolicy::DropOldest, dropped.clone());
+        for path in ["/a", "/b", "/c"] {
+            sender.send(event(path), &|| true).unwrap();
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(stream.recv().await.unwrap().path, "/b");
+
+        drop(stream);
+        assert!(sender.is_closed());
+    }
+}
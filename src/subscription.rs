@@ -0,0 +1,325 @@
+use crate::backpressure::{BackpressurePolicy, CoalesceKey, PolicySender};
+use crate::error::{FsUsageError, Result};
+use crate::stream::{self, EventStream, StreamSender};
+use crate::{Activity, EventFilter, FsEvent, MonitorEvent};
+use crossbeam_channel::Receiver;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, Weak};
+
+/// An independent feed from a shared monitor, created by
+/// `FsUsageMonitor::subscribe()` for events and `FsUsageMonitor::activity()`
+/// for file and monitor events. Dropping it unsubscribes.
+pub struct Subscription<T = FsEvent> {
+    receiver: Receiver<T>,
+    /// Tells the reader thread the subscription is still wanted, since the
+    /// channel itself stays open while its sender is alive.
+    _alive: Arc<()>,
+}
+
+impl<T> Subscription<T> {
+    pub fn events(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+
+    pub fn try_recv(&self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub fn recv(&self) -> Result<T> {
+        self.receiver
+            .recv()
+            .map_err(|_| FsUsageError::ChannelClosed)
+    }
+}
+
+enum Sink {
+    Events {
+        sender: PolicySender<FsEvent>,
+        alive: Weak<()>,
+    },
+    Stream(StreamSender),
+    /// Also receives monitor events.
+    Activity {
+        sender: PolicySender<Activity>,
+        alive: Weak<()>,
+    },
+}
+
+impl Sink {
+    /// Returns false once the receiving side is gone.
+    fn send(&mut self, event: FsEvent, keep_waiting: &dyn Fn() -> bool) -> bool {
+        match self {
+            Sink::Events { sender, alive } => {
+                let keep_waiting = || keep_waiting() && alive.strong_count() > 0;
+                sender.send(event, keep_waiting).is_ok() && alive.strong_count() > 0
+            }
+            Sink::Stream(sender) => sender.send(event, keep_waiting).is_ok() && !sender.is_closed(),
+            Sink::Activity { sender, alive } => {
+                let keep_waiting = || keep_waiting() && alive.strong_count() > 0;
+                let activity = Activity::File(Box::new(event));
+                sender.send(activity, keep_waiting).is_ok() && alive.strong_count() > 0
+            }
+        }
+    }
+
+    fn send_monitor_event(
+        &mut self,
+        event: &MonitorEvent,
+        keep_waiting: &dyn Fn() -> bool,
+    ) -> bool {
+        match self {
+            Sink::Activity { sender, alive } => {
+                let keep_waiting = || keep_waiting() && alive.strong_count() > 0;
+                let activity = Activity::Monitor(event.clone());
+                sender.send(activity, keep_waiting).is_ok() && alive.strong_count() > 0
+            }
+            _ => true,
+        }
+    }
+
+    fn flush(&mut self) -> bool {
+        match self {
+            Sink::Events { sender, alive } => sender.flush().is_ok() && alive.strong_count() > 0,
+            Sink::Stream(sender) => sender.flush().is_ok() && !sender.is_closed(),
+            Sink::Activity { sender, alive } => sender.flush().is_ok() && alive.strong_count() > 0,
+        }
+    }
+}
+
+struct Subscriber {
+    filter: Option<EventFilter>,
+    /// Locked per subscriber, so a sink blocked under
+    /// `BackpressurePolicy::Block` does not hold up new subscriptions.
+    sink: Mutex<Sink>,
+}
+
+/// Subscribers shared between a monitor and its reader thread. Each event is
+/// delivered to every subscriber whose filter matches; subscribers whose
+/// receiving side was dropped are removed on the next publish. Every
+/// subscriber gets its own channel with the monitor's capacity and
+/// backpressure policy, counting drops with the monitor's.
+#[derive(Clone)]
+pub(crate) struct Subscribers {
+    subscribers: Arc<Mutex<Vec<Arc<Subscriber>>>>,
+    capacity: Option<usize>,
+    policy: BackpressurePolicy,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscribers {
+    pub(crate) fn new(
+        capacity: Option<usize>,
+        policy: BackpressurePolicy,
+        dropped: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            capacity,
+            policy,
+            dropped,
+        }
+    }
+
+    pub(crate) fn subscribe(&self, filter: EventFilter) -> Subscription {
+        let (sender, subscription) = self.channel(self.capacity);
+        let alive = Arc::downgrade(&subscription._alive);
+        self.push(Some(filter), Sink::Events { sender, alive });
+        subscription
+    }
+
+    /// Like `subscribe`, but never drops events whatever the monitor's
+    /// capacity and policy, for collecting a bounded run in full.
+    pub(crate) fn subscribe_unbounded(&self, filter: EventFilter) -> Subscription {
+        let (sender, subscription) = self.channel(None);
+        let alive = Arc::downgrade(&subscription._alive);
+        self.push(Some(filter), Sink::Events { sender, alive });
+        subscription
+    }
+
+    pub(crate) fn subscribe_stream(&self) -> EventStream {
+        let (sender, stream) = stream::channel(self.capacity, self.policy, self.dropped.clone());
+        self.push(None, Sink::Stream(sender));
+        stream
+    }
+
+    pub(crate) fn subscribe_activity(&self) -> Subscription<Activity> {
+        let (sender, subscription) = self.channel(self.capacity);
+        let alive = Arc::downgrade(&subscription._alive);
+        self.push(None, Sink::Activity { sender, alive });
+        subscription
+    }
+
+    fn channel<T: CoalesceKey + Clone>(
+        &self,
+        capacity: Option<usize>,
+    ) -> (PolicySender<T>, Subscription<T>) {
+        let (sender, receiver) = match capacity {
+            Some(capacity) => crossbeam_channel::bounded(capacity),
+            None => crossbeam_channel::unbounded(),
+        };
+        let sender = PolicySender::new(sender, receiver.clone(), self.policy, self.dropped.clone());
+        let subscription = Subscription {
+            receiver,
+            _alive: Arc::new(()),
+        };
+        (sender, subscription)
+    }
+
+    fn push(&self, filter: Option<EventFilter>, sink: Sink) {
+        self.subscribers.lock().unwrap().push(Arc::new(Subscriber {
+            filter,
+            sink: Mutex::new(sink),
+        }));
+    }
+
+    /// Drops every sender, ending streams and subscriptions once their
+    /// queued events are consumed.
+    pub(crate) fn close(&self) {
+        self.subscribers.lock().unwrap().clear();
+    }
+
+    /// Delivers `event` to every matching subscriber. `keep_waiting` is
+    /// polled while a sink is blocked so a stopped monitor can exit.
+    /// Delivers `event` to every matching subscriber. `keep_waiting` is
+    /// polled while a sink is blocked so a stopped monitor can exit.
+    pub(crate) fn publish(&self, event: &FsEvent, keep_waiting: &dyn Fn() -> bool) {
+        self.deliver(keep_waiting, |subscriber, sink, keep_waiting| {
+            let wanted = subscriber
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter(event));
+            !wanted || sink.send(event.clone(), keep_waiting)
+        });
+    }
+
+    /// Delivers `event` to `activity()` subscribers.
+    pub(crate) fn publish_monitor_event(&self, event: &MonitorEvent) {
+        self.deliver(&|| true, |_, sink, keep_waiting| {
+            sink.send_monitor_event(event, keep_waiting)
+        });
+    }
+
+    /// Delivers events held back by `BackpressurePolicy::Coalesce`.
+    pub(crate) fn flush(&self) {
+        self.deliver(&|| true, |_, sink, _| sink.flush());
+    }
+
+    /// Runs `send` for each subscriber without holding the list lock, then
+    /// removes those for which it returned false. A blocked send also gives
+    /// up once its subscriber is removed by `close()`.
+    fn deliver(
+        &self,
+        keep_waiting: &dyn Fn() -> bool,
+        send: impl Fn(&Subscriber, &mut Sink, &dyn Fn() -> bool) -> bool,
+    ) {
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        let closed: Vec<_> = subscribers
+            .iter()
+            .filter(|subscriber| {
+                let subscribed = || {
+                    keep_waiting()
+                        && self
+                            .subscribers
+                            .lock()
+                            .unwrap()
+                            .iter()
+                            .any(|s| Arc::ptr_eq(s, subscriber))
+                };
+                !send(
+                    subscriber,
+                    &mut subscriber.sink.lock().unwrap(),
+                    &subscribed,
+                )
+            })
+            .collect();
+        if !closed.is_empty() {
+            self.subscribers
+                .lock()
+                .unwrap()
+                .retain(|subscriber| !closed.iter().any(|c| Arc::ptr_eq(c, subscriber)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(pid: u32, path: &str) -> FsEvent {
+        FsEvent {
+            pid,
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn subscribers() -> Subscribers {
+        Subscribers::new(None, BackpressurePolicy::Block, Arc::new(AtomicU64::new(0)))
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let subscribers = subscribers();
+        let audit = subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+        let rust = subscribers.subscribe(Arc::new(|e: &FsEvent| e.path.ends_with(".rs")));
+        let dropped = subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+        drop(dropped);
+        let activity = subscribers.subscribe_activity();
+
+        subscribers.publish(&event(1, "/src/main.rs"), &|| true);
+        subscribers.publish_monitor_event(&MonitorEvent::ProcessExited { pid: 1 });
+        subscribers.publish(&event(2, "/README.md"), &|| true);
+
+        let paths = |s: &Subscription| s.events().try_iter().map(|e| e.path).collect::<Vec<_>>();
+        assert_eq!(paths(&audit), vec!["/src/main.rs", "/README.md"]);
+        assert_eq!(paths(&rust), vec!["/src/main.rs"]);
+        assert!(matches!(
+            activity.events().try_iter().collect::<Vec<_>>().as_slice(),
+            [
+                Activity::File(_),
+                Activity::Monitor(MonitorEvent::ProcessExited { pid: 1 }),
+                Activity::File(_),
+            ]
+        ));
+        assert_eq!(subscribers.subscribers.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let subscribers = subscribers();
+        let subscription = subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+        let mut stream = subscribers.subscribe_stream();
+        assert_eq!(subscribers.subscribers.lock().unwrap().len(), 2);
+
+        subscribers.publish(&event(1, "/a"), &|| true);
+        subscribers.close();
+        assert!(subscribers.subscribers.lock().unwrap().is_empty());
+        assert_eq!(stream.recv().await.unwrap().path, "/a");
+        assert!(stream.recv().await.is_none());
+        assert_eq!(subscription.try_recv().unwrap().path, "/a");
+        assert!(subscription.recv().is_err());
+    }
+
+    #[test]
+    fn test_bounded_subscribers() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let subscribers =
+            Subscribers::new(Some(1), BackpressurePolicy::DropOldest, dropped.clone());
+        let subscription = subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+        subscribers.publish(&event(1, "/a"), &|| true);
+        subscribers.publish(&event(1, "/b"), &|| true);
+        assert_eq!(subscription.try_recv().unwrap().path, "/b");
+        assert_eq!(dropped.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        // A full subscriber blocks publishing until it is closed
+        let subscribers = Subscribers::new(Some(1), BackpressurePolicy::Block, dropped);
+        let _subscription = subscribers.subscribe(Arc::new(|_: &FsEvent| true));
+        subscribers.publish(&event(1, "/a"), &|| true);
+        let publisher = subscribers.clone();
+        let blocked = std::thread::spawn(move || publisher.publish(&event(1, "/b"), &|| true));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!blocked.is_finished());
+        subscribers.close();
+        blocked.join().unwrap();
+    }
+}
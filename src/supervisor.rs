@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+/// How the monitor restarts fs_usage after it exits unexpectedly (killed,
+/// a ktrace conflict, system sleep).
+///
+/// The delay before each restart starts at `initial_backoff` and doubles per
+/// consecutive failure up to `max_backoff`. A run that lasts `reset_after`
+/// counts as stable and resets the delay and the attempt count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestartPolicy {
+    /// Consecutive restarts before giving up. `None` retries forever.
+    pub max_restarts: Option<u32>,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: Some(5),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Tracks consecutive restarts under a `RestartPolicy`.
+pub(crate) struct Backoff {
+    policy: RestartPolicy,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub(crate) fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            attempts: 0,
+        }
+    }
+
+    /// Consecutive restarts so far, including the one last returned by
+    /// `next_delay`.
+    pub(crate) fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Records that fs_usage exited after running for `uptime` and returns
+    /// how long to wait before restarting it, or `None` to give up.
+    pub(crate) fn next_delay(&mut self, uptime: Duration) -> Option<Duration> {
+        if uptime >= self.policy.reset_after {
+            self.attempts = 0;
+        }
+        if self
+            .policy
+            .max_restarts
+            .is_some_and(|max| self.attempts >= max)
+        {
+            return None;
+        }
+
+        let delay = self
+            .policy
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(self.attempts.min(31)))
+            .min(self.policy.max_backoff);
+        self.attempts += 1;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(RestartPolicy {
+            max_restarts: Some(4),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            reset_after: Duration::from_secs(60),
+        });
+        let quick = Duration::from_secs(1);
+
+        let delays: Vec<_> = (0..4).map(|_| backoff.next_delay(quick)).collect();
+        assert_eq!(
+            delays,
+            [1, 2, 4, 5].map(|secs| Some(Duration::from_secs(secs)))
+        );
+        assert_eq!(backoff.attempts(), 4);
+        assert_eq!(backoff.next_delay(quick), None);
+
+        // A stable run starts over
+        assert_eq!(
+            backoff.next_delay(Duration::from_secs(60)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(backoff.attempts(), 1);
+    }
+}
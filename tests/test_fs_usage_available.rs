@@ -1,5 +1,21 @@
+#[cfg(target_os = "macos")]
+use fs_usage_sys::{FsUsageError, FsUsageMonitor};
 use std::process::Command;
 
+#[test]
+#[cfg(target_os = "macos")]
+fn test_preflight_reports_environment() {
+    let report = FsUsageMonitor::preflight();
+    assert!(report.binary.is_some(), "fs_usage ships with macOS");
+    assert!(report.os_version.is_some());
+    assert!(report.capabilities.process_details);
+
+    if !report.running_as_root {
+        assert!(!report.capabilities.file_tracing);
+        assert!(matches!(report.check(), Err(FsUsageError::NotRoot)));
+    }
+}
+
 #[test]
 #[cfg(target_os = "macos")]
 fn test_fs_usage_requires_sudo() {
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use fs_usage_sys::FsUsageMonitorBuilder;
+use fs_usage_sys::{FsUsageError, FsUsageMonitorBuilder};
 use std::thread;
 use std::time::Duration;
 
@@ -49,14 +49,11 @@ fn test_monitor_lifecycle_with_sudo() -> Result<()> {
 
     match monitor.start() {
         Ok(_) => {}
-        Err(e) => {
-            let error_msg = e.to_string();
-            if error_msg.contains("Resource busy") || error_msg.contains("ktrace_start") {
-                eprintln!("Test skipped: Another fs_usage or ktrace process is already running");
-                return Ok(());
-            }
-            return Err(e);
+        Err(e @ FsUsageError::KtraceBusy { .. }) => {
+            eprintln!("Test skipped: {}", e);
+            return Ok(());
         }
+        Err(e) => return Err(e.into()),
     }
 
     // Let it run for a bit
@@ -75,3 +72,46 @@ fn test_monitor_lifecycle_with_sudo() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg(target_os = "macos")]
+#[ignore = "requires sudo/root permissions"]
+fn test_run_command_collects_child_events() -> Result<()> {
+    let test_dir = std::path::PathBuf::from("target/test_run_command");
+    std::fs::create_dir_all(&test_dir)?;
+    let test_file = test_dir.canonicalize()?.join("touched.txt");
+    let _ = std::fs::remove_file(&test_file);
+
+    let mut monitor = FsUsageMonitorBuilder::new().build()?;
+
+    let mut command = std::process::Command::new("/bin/sh");
+    command
+        .arg("-c")
+        .arg(format!("touch {}", test_file.display()));
+
+    let output = match monitor.run_command(command) {
+        Ok(output) => output,
+        Err(e @ FsUsageError::KtraceBusy { .. }) => {
+            eprintln!("Test skipped: {}", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    assert!(output.status.success());
+    assert!(!monitor.is_running());
+
+    // The touch runs in a child of the shell, so this also exercises tree following
+    assert!(
+        output
+            .events
+            .iter()
+            .any(|event| event.path.ends_with("touched.txt")),
+        "Expected an event for the touched file, got {} events",
+        output.events.len()
+    );
+
+    let _ = std::fs::remove_file(&test_file);
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use fs_usage_sys::FsUsageMonitorBuilder;
+use fs_usage_sys::{FsUsageError, FsUsageMonitorBuilder};
 use std::fs;
 use std::path::PathBuf;
 use std::thread;
@@ -31,14 +31,11 @@ fn test_captures_write_operations() -> Result<()> {
 
     match monitor.start() {
         Ok(_) => {}
-        Err(e) => {
-            let error_msg = e.to_string();
-            if error_msg.contains("Resource busy") || error_msg.contains("ktrace_start") {
-                eprintln!("Test skipped: Another fs_usage or ktrace process is already running");
-                return Ok(());
-            }
-            return Err(e);
+        Err(e @ FsUsageError::KtraceBusy { .. }) => {
+            eprintln!("Test skipped: {}", e);
+            return Ok(());
         }
+        Err(e) => return Err(e.into()),
     }
     let events = monitor.events();
 
@@ -145,14 +142,11 @@ fn test_write_only_filter() -> Result<()> {
 
     match monitor.start() {
         Ok(_) => {}
-        Err(e) => {
-            let error_msg = e.to_string();
-            if error_msg.contains("Resource busy") || error_msg.contains("ktrace_start") {
-                eprintln!("Test skipped: Another fs_usage or ktrace process is already running");
-                return Ok(());
-            }
-            return Err(e);
+        Err(e @ FsUsageError::KtraceBusy { .. }) => {
+            eprintln!("Test skipped: {}", e);
+            return Ok(());
         }
+        Err(e) => return Err(e.into()),
     }
     let events = monitor.events();
 